@@ -0,0 +1,47 @@
+use alloc::vec::Vec;
+use core::{error::Error, fmt};
+
+#[derive(Debug)]
+pub struct BroadcastError<A, E> {
+    pub failures: Vec<(A, E)>,
+}
+
+impl<A, E> BroadcastError<A, E> {
+    pub fn check(failures: Vec<(A, E)>) -> Result<(), Self> {
+        if failures.is_empty() {
+            return Ok(());
+        }
+        Err(Self { failures })
+    }
+}
+
+impl<A, E> BroadcastError<A, E>
+where
+    A: fmt::Debug + fmt::Display + 'static,
+    E: fmt::Debug + fmt::Display + 'static,
+{
+    pub fn from_error<'a>(err: &'a (dyn Error + 'static)) -> Option<&'a Self> {
+        err.downcast_ref()
+    }
+}
+
+impl<A: fmt::Display, E: fmt::Display> fmt::Display for BroadcastError<A, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "broadcast failed for {} address(es)",
+            self.failures.len()
+        )?;
+        for (addr, err) in self.failures.iter() {
+            write!(f, "; {addr}: {err}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<A, E> Error for BroadcastError<A, E>
+where
+    A: fmt::Debug + fmt::Display,
+    E: fmt::Debug + fmt::Display,
+{
+}
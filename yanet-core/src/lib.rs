@@ -2,7 +2,19 @@
 #![feature(async_fn_in_trait)]
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+mod broadcast;
+#[cfg(feature = "alloc")]
+pub use broadcast::BroadcastError;
 pub mod socket;
-pub use socket::Socket;
+pub use socket::{Either, Lenient, Or, OrError, Socket};
 pub mod service;
 pub use service::{Service, ServiceName};
+mod trace;
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing;
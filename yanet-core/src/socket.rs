@@ -1,5 +1,4 @@
-use futures_micro::Future;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 
 pub trait Socket: Sized {
     type Addr;
@@ -12,12 +11,19 @@ pub trait Socket: Sized {
     where
         D: Serialize + ?Sized;
 
+    /// Must be cancel safe: dropping the future before it completes may not
+    /// lose a datagram or protocol state, because wrappers such as the muxer
+    /// interrupt a pending `recv` to let a send through.
     async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
     where
         D: DeserializeOwned;
 
     fn or<S: Socket>(self, other: S) -> Or<Self, S> {
-        Or { this: self, other }
+        Or {
+            this: self,
+            other,
+            other_first: false,
+        }
     }
 
     fn then<R>(self, then: impl FnOnce(Self) -> R) -> R {
@@ -28,26 +34,39 @@ pub trait Socket: Sized {
 pub struct Or<T, O> {
     this: T,
     other: O,
+    other_first: bool,
 }
 
-#[derive(PartialEq, PartialOrd, Ord, Eq)]
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq)]
 pub enum Either<T, O> {
     This(T),
     Other(O),
 }
 
+#[derive(Debug)]
+pub enum OrError<T, O> {
+    This(T),
+    Other(O),
+    Both(T, O),
+}
+
 impl<T: Socket, O: Socket> Socket for Or<T, O> {
     type Addr = Either<T::Addr, O::Addr>;
 
-    type Error = Either<T::Error, O::Error>;
+    type Error = OrError<T::Error, O::Error>;
 
     async fn broadcast<D>(&mut self, data: &D) -> Result<(), Self::Error>
     where
         D: Serialize,
     {
-        self.this.broadcast(data).await.map_err(Either::This)?;
-        self.other.broadcast(data).await.map_err(Either::Other)?;
-        Ok(())
+        let this = self.this.broadcast(data).await;
+        let other = self.other.broadcast(data).await;
+        match (this, other) {
+            (Ok(()), Ok(())) => Ok(()),
+            (Err(this), Ok(())) => Err(OrError::This(this)),
+            (Ok(()), Err(other)) => Err(OrError::Other(other)),
+            (Err(this), Err(other)) => Err(OrError::Both(this, other)),
+        }
     }
 
     async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
@@ -55,8 +74,8 @@ impl<T: Socket, O: Socket> Socket for Or<T, O> {
         D: Serialize + ?Sized,
     {
         match addr {
-            Either::This(addr) => Ok(self.this.send(data, addr).await.map_err(Either::This)?),
-            Either::Other(addr) => Ok(self.other.send(data, addr).await.map_err(Either::Other)?),
+            Either::This(addr) => Ok(self.this.send(data, addr).await.map_err(OrError::This)?),
+            Either::Other(addr) => Ok(self.other.send(data, addr).await.map_err(OrError::Other)?),
         }
     }
 
@@ -64,29 +83,32 @@ impl<T: Socket, O: Socket> Socket for Or<T, O> {
     where
         D: DeserializeOwned,
     {
-        loop {
-            {
-                let future = self.this.recv();
-                futures_micro::pin!(future);
-                let waker = futures_micro::waker().await;
-                let mut context = futures_micro::Context::from_waker(&waker);
-                match future.poll(&mut context) {
-                    futures_micro::Poll::Ready(Ok((s, a))) => return Ok((s, Either::This(a))),
-                    futures_micro::Poll::Ready(Err(e)) => return Err(Either::This(e)),
-                    futures_micro::Poll::Pending => (),
-                }
-            }
-            {
-                let future = self.other.recv();
-                futures_micro::pin!(future);
-                let waker = futures_micro::waker().await;
-                let mut context = futures_micro::Context::from_waker(&waker);
-                match future.poll(&mut context) {
-                    futures_micro::Poll::Ready(Ok((s, a))) => return Ok((s, Either::Other(a))),
-                    futures_micro::Poll::Ready(Err(e)) => return Err(Either::Other(e)),
-                    futures_micro::Poll::Pending => (),
-                }
-            }
+        // Alternate which side is polled first so a busy side cannot starve the other.
+        self.other_first = !self.other_first;
+        let (this, other) = (&mut self.this, &mut self.other);
+        let this = async {
+            let (s, a) = this.recv().await.map_err(OrError::This)?;
+            Ok((s, Either::This(a)))
+        };
+        let other = async {
+            let (s, a) = other.recv().await.map_err(OrError::Other)?;
+            Ok((s, Either::Other(a)))
+        };
+        if self.other_first {
+            futures_micro::or!(other, this).await
+        } else {
+            futures_micro::or!(this, other).await
         }
     }
 }
+
+/// Receives as `None` instead of failing when a datagram does not decode, so a
+/// receive loop can skip bad input while still returning transport errors.
+#[derive(Debug)]
+pub struct Lenient<T>(pub Option<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Lenient<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self(T::deserialize(deserializer).ok()))
+    }
+}
@@ -0,0 +1,77 @@
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __discard {
+    () => {};
+    ($msg:literal $(, $arg:expr)* $(,)?) => {
+        let _ = format_args!($msg $(, $arg)*);
+    };
+    (?$v:expr $(, $($rest:tt)*)?) => {
+        let _ = &$v;
+        $crate::__discard!($($($rest)*)?);
+    };
+    (%$v:expr $(, $($rest:tt)*)?) => {
+        let _ = &$v;
+        $crate::__discard!($($($rest)*)?);
+    };
+    ($k:ident = ?$v:expr $(, $($rest:tt)*)?) => {
+        let _ = &$v;
+        $crate::__discard!($($($rest)*)?);
+    };
+    ($k:ident = %$v:expr $(, $($rest:tt)*)?) => {
+        let _ = &$v;
+        $crate::__discard!($($($rest)*)?);
+    };
+    ($k:ident = $v:expr $(, $($rest:tt)*)?) => {
+        let _ = &$v;
+        $crate::__discard!($($($rest)*)?);
+    };
+    ($k:ident $(, $($rest:tt)*)?) => {
+        let _ = &$k;
+        $crate::__discard!($($($rest)*)?);
+    };
+}
+
+macro_rules! level {
+    ($d:tt $name:ident) => {
+        #[cfg(feature = "tracing")]
+        #[macro_export]
+        macro_rules! $name {
+                                    ($d($d arg:tt)*) => { $crate::tracing::$name!($d($d arg)*) };
+                                }
+
+        #[cfg(not(feature = "tracing"))]
+        #[macro_export]
+        macro_rules! $name {
+                                    ($d($d arg:tt)*) => {
+                                        if false {
+                                            $crate::__discard!($d($d arg)*);
+                                        }
+                                    };
+                                }
+    };
+}
+
+level!($ trace);
+level!($ debug);
+level!($ info);
+level!($ warn);
+level!($ error);
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! instrument {
+    ($fut:expr, $($span:tt)*) => {
+        $crate::tracing::Instrument::instrument($fut, $crate::tracing::info_span!($($span)*))
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! instrument {
+    ($fut:expr, $name:literal $(, $($field:tt)*)?) => {{
+        if false {
+            $crate::__discard!($($($field)*)?);
+        }
+        $fut
+    }};
+}
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use event_listener::Event;
+
+// Senders announce themselves here so a dispatcher blocked in `recv` gives up
+// the socket lock instead of holding it until the next frame arrives.
+#[derive(Default)]
+pub(crate) struct Gate {
+    senders: AtomicUsize,
+    event: Event,
+}
+
+pub(crate) struct Waiting<'a>(&'a Gate);
+
+impl Gate {
+    pub(crate) fn wait(&self) -> Waiting<'_> {
+        self.senders.fetch_add(1, Ordering::SeqCst);
+        self.event.notify(usize::MAX);
+        Waiting(self)
+    }
+
+    pub(crate) fn wake(&self) {
+        self.event.notify(usize::MAX);
+    }
+
+    pub(crate) async fn idle(&self) {
+        loop {
+            let listener = self.event.listen();
+            if self.senders.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            listener.await;
+        }
+    }
+
+    pub(crate) async fn interrupted(&self) {
+        let listener = self.event.listen();
+        if self.senders.load(Ordering::SeqCst) == 0 {
+            listener.await;
+        }
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.senders.fetch_sub(1, Ordering::SeqCst);
+        self.0.event.notify(usize::MAX);
+    }
+}
@@ -1,31 +1,259 @@
 #![allow(incomplete_features)]
 #![feature(async_fn_in_trait)]
 
-use std::{cell::RefCell, rc::Rc};
+use std::collections::{BTreeMap, BTreeSet};
 
 use async_channel::{bounded, Receiver, Sender};
-use dashmap::DashMap;
-use serde::Serialize;
+use async_lock::{Mutex, MutexGuard};
+use dashmap::{mapref::one::RefMut, DashMap};
+use serde::{Deserialize, Serialize};
 
-use yanet_core::{Service, Socket};
+use yanet_core::{Lenient, Service, Socket};
+
+use gate::Gate;
+
+mod gate;
+mod policy;
+mod stream;
+pub use policy::Policy;
+pub use stream::MuxerStream;
 
 #[derive(Debug)]
 pub enum Error<E> {
     InternalClosed,
+    StreamClosed,
     Socket(E),
     Serde(postcard::Error),
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+enum Frame {
+    Datagram(Vec<u8>),
+    Stream(u32, bool, Vec<u8>),
+    Close(u32, bool),
+    Denied,
+}
+
+#[cfg(not(feature = "sync"))]
+type Ref<T> = std::rc::Rc<T>;
+#[cfg(feature = "sync")]
+type Ref<T> = std::sync::Arc<T>;
+
+type Incoming<A> = (String, A, u32, Receiver<Vec<u8>>);
+type Closing<A> = (String, u32, bool, A);
+type Pair<T> = (Sender<T>, Receiver<T>);
+
+struct Handler<A> {
+    datagrams: Sender<(Vec<u8>, A)>,
+    incoming: Sender<Incoming<A>>,
+    streams: BTreeMap<(A, u32, bool), Sender<Vec<u8>>>,
+    next_stream: u32,
+    policy: Policy<A>,
+    denied: u64,
+    contacted: BTreeSet<A>,
+    broadcasted: bool,
+    rejected: BTreeSet<A>,
+}
+
+struct Shared<S: Socket> {
+    socket: Ref<Mutex<S>>,
+    handlers: Ref<DashMap<String, Handler<S::Addr>>>,
+    closing: Pair<Closing<S::Addr>>,
+    gate: Ref<Gate>,
+}
+
+impl<S: Socket> Clone for Shared<S> {
+    fn clone(&self) -> Self {
+        Self {
+            socket: self.socket.clone(),
+            handlers: self.handlers.clone(),
+            closing: self.closing.clone(),
+            gate: self.gate.clone(),
+        }
+    }
+}
+
+impl<S: Socket> Shared<S> {
+    fn route(&self, name: &str) -> Option<RefMut<'_, String, Handler<S::Addr>>> {
+        let mut name = name;
+        loop {
+            if let Some(handler) = self.handlers.get_mut(name) {
+                return Some(handler);
+            }
+            name = &name[..name.rfind('/')?];
+        }
+    }
+}
+
+impl<S> Shared<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone,
+{
+    async fn send_frame(
+        &self,
+        name: &str,
+        frame: Frame,
+        addr: S::Addr,
+    ) -> Result<(), Error<S::Error>> {
+        self.flush_closing().await;
+        self.send_raw(name, frame, addr).await
+    }
+
+    async fn flush_closing(&self) {
+        while let Ok((name, id, initiator, addr)) = self.closing.1.try_recv() {
+            yanet_core::debug!(service = %name, id, "closing dropped stream");
+            self.send_raw(&name, Frame::Close(id, initiator), addr)
+                .await
+                .ok();
+        }
+    }
+
+    async fn send_raw(
+        &self,
+        name: &str,
+        frame: Frame,
+        addr: S::Addr,
+    ) -> Result<(), Error<S::Error>> {
+        self.lock_send()
+            .await
+            .send(&(name, frame), addr)
+            .await
+            .map_err(Error::Socket)
+    }
+
+    async fn lock_send(&self) -> MutexGuard<'_, S> {
+        let _waiting = self.gate.wait();
+        self.socket.lock().await
+    }
+
+    async fn recv_frame(&self) -> Option<Result<(Lenient<(String, Frame)>, S::Addr), S::Error>> {
+        self.gate.idle().await;
+        let mut socket = self.socket.lock().await;
+        let recv = async { Some(socket.recv().await) };
+        let interrupted = async {
+            self.gate.interrupted().await;
+            None
+        };
+        futures_micro::or!(recv, interrupted).await
+    }
+
+    async fn dispatch<T>(&self) -> Result<T, Error<S::Error>> {
+        loop {
+            self.flush_closing().await;
+            let Some(received) = self.recv_frame().await else {
+                continue;
+            };
+            let (Lenient(frame), addr) = received.map_err(Error::Socket)?;
+            let Some((name, frame)) = frame else {
+                yanet_core::debug!("dropping unreadable frame");
+                continue;
+            };
+            let Some(mut handler) = self.route(&name) else {
+                yanet_core::debug!(service = %name, "dropping frame for unknown service");
+                continue;
+            };
+            // A broadcast may reach any peer, so any of them may answer it.
+            let expected = match frame {
+                Frame::Denied => handler.broadcasted || handler.contacted.contains(&addr),
+                _ => false,
+            };
+            if !expected && !handler.policy.permits(&addr) {
+                handler.denied += 1;
+                let reply = handler.policy.replies();
+                drop(handler);
+                yanet_core::debug!(service = %name, "denied frame from peer");
+                // Answering a Denied or Close could bounce between two peers
+                // that deny each other forever.
+                let reply = match frame {
+                    _ if !reply => continue,
+                    Frame::Denied | Frame::Close(..) => continue,
+                    Frame::Stream(id, initiator, _) => Frame::Close(id, !initiator),
+                    _ => Frame::Denied,
+                };
+                self.send_frame(&name, reply, addr).await.ok();
+                continue;
+            }
+            // Channels are never awaited here, so one service that stops reading
+            // cannot hold up frames for every other service on the muxer.
+            match frame {
+                Frame::Datagram(vec) => {
+                    yanet_core::trace!(service = %name, ?vec, "received datagram");
+                    if handler.datagrams.try_send((vec, addr)).is_err() {
+                        yanet_core::debug!(service = %name, "dropping datagram, service not reading");
+                    }
+                }
+                Frame::Stream(id, initiator, vec) => {
+                    let key = (addr.clone(), id, !initiator);
+                    let sender = match handler.streams.get(&key) {
+                        Some(sender) => sender.clone(),
+                        None if initiator => {
+                            let (tx, rx) = bounded(10);
+                            let incoming = (name.clone(), addr.clone(), id, rx);
+                            if handler.incoming.try_send(incoming).is_err() {
+                                drop(handler);
+                                yanet_core::debug!(service = %name, id, "refusing stream, service not accepting");
+                                let close = Frame::Close(id, !initiator);
+                                self.send_frame(&name, close, addr).await.ok();
+                                continue;
+                            }
+                            yanet_core::debug!(service = %name, id, "incoming stream");
+                            handler.streams.insert(key, tx.clone());
+                            tx
+                        }
+                        None => {
+                            yanet_core::debug!(service = %name, id, "dropping frame for unknown stream");
+                            continue;
+                        }
+                    };
+                    drop(handler);
+                    yanet_core::trace!(service = %name, id, ?vec, "received stream frame");
+                    if sender.try_send(vec).is_err() {
+                        yanet_core::debug!(service = %name, id, "dropping stream frame, stream not reading");
+                    }
+                }
+                Frame::Close(id, initiator) => {
+                    yanet_core::debug!(service = %name, id, "stream closed by peer");
+                    handler.streams.remove(&(addr, id, !initiator));
+                }
+                Frame::Denied => {
+                    yanet_core::debug!(service = %name, "denied by peer");
+                    handler.rejected.insert(addr);
+                }
+            }
+        }
+    }
+}
+
 pub struct Muxer<S: Socket> {
-    socket: Rc<RefCell<S>>,
-    handlers: Rc<DashMap<String, Sender<(Vec<u8>, S::Addr)>>>,
+    shared: Shared<S>,
+    prefix: String,
 }
 
 impl<S: Socket> Muxer<S> {
     pub fn new(socket: S) -> Self {
         Self {
-            socket: Rc::new(RefCell::new(socket)),
-            handlers: Default::default(),
+            shared: Shared {
+                socket: Ref::new(Mutex::new(socket)),
+                handlers: Default::default(),
+                closing: async_channel::unbounded(),
+                gate: Default::default(),
+            },
+            prefix: String::new(),
+        }
+    }
+
+    pub fn namespace(&self, name: impl ToString) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            prefix: self.qualify(name),
+        }
+    }
+
+    fn qualify(&self, name: impl ToString) -> String {
+        match self.prefix.as_str() {
+            "" => name.to_string(),
+            prefix => format!("{}/{}", prefix, name.to_string()),
         }
     }
 
@@ -34,28 +262,153 @@ impl<S: Socket> Muxer<S> {
         U: Service<MuxerSocket<S>>,
         U::Name: ToString,
     {
-        let name = upgrader.name().to_string();
+        self.handle_with_policy(upgrader, Policy::allow_all()).await
+    }
+
+    pub async fn handle_with_policy<U>(
+        &self,
+        upgrader: U,
+        policy: Policy<S::Addr>,
+    ) -> Result<U::Output, U::Error>
+    where
+        U: Service<MuxerSocket<S>>,
+        U::Name: ToString,
+    {
+        let socket = self.socket_with_policy(upgrader.name(), policy);
+        let span_name = socket.name.clone();
+        yanet_core::instrument!(upgrader.upgrade(socket), "service", name = %span_name).await
+    }
+
+    pub fn socket(&self, name: impl ToString) -> MuxerSocket<S> {
+        self.socket_with_policy(name, Policy::allow_all())
+    }
+
+    pub fn socket_with_policy(
+        &self,
+        name: impl ToString,
+        policy: Policy<S::Addr>,
+    ) -> MuxerSocket<S> {
+        let name = self.qualify(name);
         let (tx, rx) = bounded(10);
-        self.handlers.insert(name.clone(), tx);
-        let socket = MuxerSocket {
+        let (incoming_tx, incoming_rx) = bounded(10);
+        self.shared.handlers.insert(
+            name.clone(),
+            Handler {
+                datagrams: tx,
+                incoming: incoming_tx,
+                streams: BTreeMap::new(),
+                next_stream: 0,
+                policy,
+                denied: 0,
+                contacted: BTreeSet::new(),
+                broadcasted: false,
+                rejected: BTreeSet::new(),
+            },
+        );
+        MuxerSocket {
             name,
-            socket: self.socket.clone(),
-            handlers: self.handlers.clone(),
+            shared: self.shared.clone(),
             receiver: rx,
-        };
-        upgrader.upgrade(socket).await
+            incoming: incoming_rx,
+        }
     }
 }
 
-#[derive(Clone)]
 pub struct MuxerSocket<S: Socket> {
     name: String,
-    socket: Rc<RefCell<S>>,
-    handlers: Rc<DashMap<String, Sender<(Vec<u8>, S::Addr)>>>,
+    shared: Shared<S>,
     receiver: Receiver<(Vec<u8>, S::Addr)>,
+    incoming: Receiver<Incoming<S::Addr>>,
+}
+
+impl<S: Socket> Clone for MuxerSocket<S> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            shared: self.shared.clone(),
+            receiver: self.receiver.clone(),
+            incoming: self.incoming.clone(),
+        }
+    }
+}
+
+impl<S> MuxerSocket<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone,
+{
+    pub fn denied(&self) -> u64 {
+        self.shared
+            .handlers
+            .get(&self.name)
+            .map_or(0, |handler| handler.denied)
+    }
+
+    pub fn rejected_by(&self) -> Vec<S::Addr> {
+        self.shared
+            .handlers
+            .get(&self.name)
+            .map_or(Vec::new(), |handler| {
+                handler.rejected.iter().cloned().collect()
+            })
+    }
+
+    fn contact(&self, addr: &S::Addr) {
+        if let Some(mut handler) = self.shared.handlers.get_mut(&self.name) {
+            if !handler.contacted.contains(addr) {
+                handler.contacted.insert(addr.clone());
+            }
+        }
+    }
+
+    pub fn open_stream(&self, addr: S::Addr) -> Result<MuxerStream<S>, Error<S::Error>> {
+        let (tx, rx) = bounded(10);
+        let mut handler = self
+            .shared
+            .handlers
+            .get_mut(&self.name)
+            .ok_or(Error::InternalClosed)?;
+        let id = handler.next_stream;
+        handler.next_stream = id.wrapping_add(1);
+        handler.streams.insert((addr.clone(), id, true), tx);
+        handler.contacted.insert(addr.clone());
+        yanet_core::debug!(service = %self.name, id, "opening stream");
+        Ok(MuxerStream::new(
+            self.name.clone(),
+            self.shared.clone(),
+            addr,
+            id,
+            true,
+            rx,
+        ))
+    }
+
+    pub async fn accept(&self) -> Result<MuxerStream<S>, Error<S::Error>> {
+        let task1 = self.shared.dispatch();
+        let task2 = async {
+            let (name, addr, id, rx) = self
+                .incoming
+                .recv()
+                .await
+                .map_err(|_| Error::InternalClosed)?;
+            Ok(MuxerStream::new(
+                name,
+                self.shared.clone(),
+                addr,
+                id,
+                false,
+                rx,
+            ))
+        };
+        futures_micro::or!(task1, task2).await
+    }
 }
 
-impl<S: Socket> Socket for MuxerSocket<S> {
+impl<S> Socket for MuxerSocket<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone,
+{
     type Addr = S::Addr;
     type Error = Error<S::Error>;
 
@@ -63,11 +416,15 @@ impl<S: Socket> Socket for MuxerSocket<S> {
     where
         D: Serialize,
     {
-        let msg = postcard::to_allocvec(data).unwrap();
-        println!("broadcasting {:?}", msg);
-        self.socket
-            .borrow_mut()
-            .broadcast(&(self.name.as_str(), msg))
+        let msg = postcard::to_allocvec(data).map_err(Error::Serde)?;
+        yanet_core::trace!(service = %self.name, ?msg, "broadcasting");
+        if let Some(mut handler) = self.shared.handlers.get_mut(&self.name) {
+            handler.broadcasted = true;
+        }
+        self.shared
+            .lock_send()
+            .await
+            .broadcast(&(self.name.as_str(), Frame::Datagram(msg)))
             .await
             .map_err(Error::Socket)?;
         Ok(())
@@ -77,40 +434,24 @@ impl<S: Socket> Socket for MuxerSocket<S> {
         D: Serialize + ?Sized,
     {
         let msg = postcard::to_allocvec(data).map_err(Error::Serde)?;
-        println!("sending {:?}", msg);
-        self.socket
-            .borrow_mut()
-            .send(&(self.name.as_str(), msg), addr)
+        yanet_core::trace!(service = %self.name, ?msg, "sending");
+        self.contact(&addr);
+        self.shared
+            .send_frame(&self.name, Frame::Datagram(msg), addr)
             .await
-            .map_err(Error::Socket)?;
-        Ok(())
     }
 
     async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
     where
         D: serde::de::DeserializeOwned,
     {
-        let task1 = async {
-            loop {
-                if let Ok(((name, vec), addr)) =
-                    self.socket.borrow_mut().recv::<(String, Vec<u8>)>().await
-                {
-                    if let Some(sender) = self.handlers.get(&name) {
-                        sender
-                            .send((vec, addr))
-                            .await
-                            .map_err(|_| Error::InternalClosed)?;
-                    }
-                }
-            }
-        };
+        let task1 = self.shared.dispatch();
         let task2 = async {
             let (vec, addr) = self
                 .receiver
                 .recv()
                 .await
                 .map_err(|_| Error::InternalClosed)?;
-            println!("received {:?}", vec);
             let dat = postcard::from_bytes(&vec).map_err(Error::Serde)?;
             Ok((dat, addr))
         };
@@ -0,0 +1,60 @@
+use std::collections::BTreeSet;
+
+enum Allow<A> {
+    Any,
+    Peers(BTreeSet<A>),
+    Predicate(Box<dyn Fn(&A) -> bool + Send + Sync>),
+}
+
+pub struct Policy<A> {
+    allow: Allow<A>,
+    reply: bool,
+}
+
+impl<A> Policy<A> {
+    pub fn allow_all() -> Self {
+        Self {
+            allow: Allow::Any,
+            reply: false,
+        }
+    }
+
+    pub fn allow_if(predicate: impl Fn(&A) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            allow: Allow::Predicate(Box::new(predicate)),
+            reply: false,
+        }
+    }
+
+    pub fn reply_denied(mut self, reply: bool) -> Self {
+        self.reply = reply;
+        self
+    }
+
+    pub(crate) fn replies(&self) -> bool {
+        self.reply
+    }
+}
+
+impl<A: Ord> Policy<A> {
+    pub fn allow_peers(peers: impl IntoIterator<Item = A>) -> Self {
+        Self {
+            allow: Allow::Peers(peers.into_iter().collect()),
+            reply: false,
+        }
+    }
+
+    pub(crate) fn permits(&self, addr: &A) -> bool {
+        match &self.allow {
+            Allow::Any => true,
+            Allow::Peers(peers) => peers.contains(addr),
+            Allow::Predicate(predicate) => predicate(addr),
+        }
+    }
+}
+
+impl<A> Default for Policy<A> {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
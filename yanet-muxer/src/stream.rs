@@ -0,0 +1,114 @@
+use async_channel::Receiver;
+use serde::{de::DeserializeOwned, Serialize};
+use yanet_core::Socket;
+
+use crate::{Error, Frame, Shared};
+
+pub struct MuxerStream<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone,
+{
+    name: String,
+    shared: Shared<S>,
+    addr: S::Addr,
+    id: u32,
+    initiator: bool,
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl<S> MuxerStream<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone,
+{
+    pub(crate) fn new(
+        name: String,
+        shared: Shared<S>,
+        addr: S::Addr,
+        id: u32,
+        initiator: bool,
+        receiver: Receiver<Vec<u8>>,
+    ) -> Self {
+        Self {
+            name,
+            shared,
+            addr,
+            id,
+            initiator,
+            receiver,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn peer(&self) -> &S::Addr {
+        &self.addr
+    }
+
+    pub async fn send<D>(&mut self, data: &D) -> Result<(), Error<S::Error>>
+    where
+        D: Serialize + ?Sized,
+    {
+        let msg = postcard::to_allocvec(data).map_err(Error::Serde)?;
+        yanet_core::trace!(service = %self.name, id = self.id, ?msg, "sending stream frame");
+        let frame = Frame::Stream(self.id, self.initiator, msg);
+        self.shared
+            .send_frame(&self.name, frame, self.addr.clone())
+            .await
+    }
+
+    fn unregister(&self) -> bool {
+        let Some(mut handler) = self.shared.route(&self.name) else {
+            return false;
+        };
+        let key = (self.addr.clone(), self.id, self.initiator);
+        handler.streams.remove(&key).is_some()
+    }
+
+    pub async fn recv<D>(&mut self) -> Result<D, Error<S::Error>>
+    where
+        D: DeserializeOwned,
+    {
+        let task1 = self.shared.dispatch();
+        let task2 = async {
+            let vec = self
+                .receiver
+                .recv()
+                .await
+                .map_err(|_| Error::StreamClosed)?;
+            postcard::from_bytes(&vec).map_err(Error::Serde)
+        };
+        futures_micro::or!(task1, task2).await
+    }
+
+    pub async fn close(self) -> Result<(), Error<S::Error>> {
+        yanet_core::debug!(service = %self.name, id = self.id, "closing stream");
+        self.unregister();
+        let frame = Frame::Close(self.id, self.initiator);
+        self.shared
+            .send_frame(&self.name, frame, self.addr.clone())
+            .await
+    }
+}
+
+impl<S> Drop for MuxerStream<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone,
+{
+    fn drop(&mut self) {
+        if self.unregister() {
+            let closing = (
+                self.name.clone(),
+                self.id,
+                self.initiator,
+                self.addr.clone(),
+            );
+            self.shared.closing.0.try_send(closing).ok();
+            self.shared.gate.wake();
+        }
+    }
+}
@@ -0,0 +1,62 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{future::Future, time::Duration};
+
+use futures_lite::future::{block_on, or};
+use yanet_core::Socket;
+use yanet_muxer::Muxer;
+use yanet_sim::{Network, SimSocket};
+
+fn within<T>(future: impl Future<Output = T>) -> T {
+    let timeout = async {
+        futures_timer::Delay::new(Duration::from_secs(2)).await;
+        panic!("timed out");
+    };
+    block_on(or(future, timeout))
+}
+
+fn pair() -> (Muxer<SimSocket>, Muxer<SimSocket>, yanet_sim::NodeId) {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let b_id = b.id();
+    (Muxer::new(a), Muxer::new(b), b_id)
+}
+
+#[test]
+fn same_service_in_two_namespaces_stays_apart() {
+    let (a, b, b_id) = pair();
+    let mut to_app2 = a.namespace("app2").socket("pinger");
+    let mut app1 = b.namespace("app1").socket("pinger");
+    let mut app2 = b.namespace("app2").socket("pinger");
+    within(async {
+        to_app2.send(&2u32, b_id).await.unwrap();
+        let unread = async {
+            app1.recv::<u32>().await.unwrap();
+            unreachable!("app1 is never addressed");
+        };
+        assert_eq!(or(app2.recv::<u32>(), unread).await.unwrap().0, 2);
+    });
+}
+
+#[test]
+fn nested_namespaces_match_the_joined_name() {
+    let (a, b, b_id) = pair();
+    let mut sender = a.socket("app/inner/svc");
+    let mut nested = b.namespace("app").namespace("inner").socket("svc");
+    within(async {
+        sender.send(&1u32, b_id).await.unwrap();
+        assert_eq!(nested.recv::<u32>().await.unwrap().0, 1);
+    });
+}
+
+#[test]
+fn unknown_service_falls_back_to_its_namespace() {
+    let (a, b, b_id) = pair();
+    let mut sender = a.namespace("app1").socket("missing");
+    let mut app1 = b.socket("app1");
+    within(async {
+        sender.send(&3u32, b_id).await.unwrap();
+        assert_eq!(app1.recv::<u32>().await.unwrap().0, 3);
+    });
+}
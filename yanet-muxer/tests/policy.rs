@@ -0,0 +1,89 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{future::Future, time::Duration};
+
+use futures_lite::future::{block_on, or};
+use yanet_core::Socket;
+use yanet_muxer::{Muxer, Policy};
+use yanet_sim::Network;
+
+fn within<T>(future: impl Future<Output = T>) -> T {
+    let timeout = async {
+        futures_timer::Delay::new(Duration::from_secs(2)).await;
+        panic!("timed out");
+    };
+    block_on(or(future, timeout))
+}
+
+async fn settle() {
+    futures_timer::Delay::new(Duration::from_millis(100)).await;
+}
+
+#[test]
+fn denied_peer_is_counted_and_answered() {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let b_id = b.id();
+    let (a, b) = (Muxer::new(a), Muxer::new(b));
+    let mut sender = a.socket("svc");
+    let mut guarded = b.socket_with_policy("svc", Policy::allow_peers([]).reply_denied(true));
+    within(async {
+        sender.send(&1u32, b_id).await.unwrap();
+        let pump = async {
+            or(sender.recv::<u32>(), guarded.recv::<u32>())
+                .await
+                .unwrap();
+            unreachable!("every datagram is denied");
+        };
+        or(pump, settle()).await;
+    });
+    assert_eq!(guarded.denied(), 1);
+    assert_eq!(sender.rejected_by(), vec![b_id]);
+}
+
+#[test]
+fn mutually_denying_peers_do_not_loop() {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let (a, b) = (Muxer::new(a), Muxer::new(b));
+    let deny = |peer| Policy::allow_if(move |addr| *addr != peer).reply_denied(true);
+    let mut a = a.socket_with_policy("svc", deny(b_id));
+    let mut b = b.socket_with_policy("svc", deny(a_id));
+    within(async {
+        a.broadcast(&1u32).await.unwrap();
+        let pump = async {
+            or(a.recv::<u32>(), b.recv::<u32>()).await.unwrap();
+            unreachable!("every datagram is denied");
+        };
+        or(pump, settle()).await;
+    });
+    assert!(net.stats().sent <= 2, "sent {}", net.stats().sent);
+    assert_eq!(b.denied(), 1);
+    assert_eq!(a.denied(), 0);
+    assert_eq!(a.rejected_by(), vec![b_id]);
+}
+
+#[test]
+fn close_from_a_denied_peer_is_not_answered() {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let (a, b) = (Muxer::new(a), Muxer::new(b));
+    let deny = |peer| Policy::allow_if(move |addr| *addr != peer).reply_denied(true);
+    let mut a = a.socket_with_policy("svc", deny(b_id));
+    let mut b = b.socket_with_policy("svc", deny(a_id));
+    within(async {
+        let mut stream = a.open_stream(b_id).unwrap();
+        stream.send(&1u32).await.unwrap();
+        let pump = async {
+            or(a.recv::<u32>(), b.recv::<u32>()).await.unwrap();
+            unreachable!("every datagram is denied");
+        };
+        or(pump, settle()).await;
+    });
+    // The stream frame, the Close refusing it, and nothing after that.
+    assert_eq!(net.stats().sent, 2);
+    assert_eq!((a.denied(), b.denied()), (1, 1));
+}
@@ -0,0 +1,127 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{future::Future, time::Duration};
+
+use futures_lite::future::{block_on, or};
+use yanet_core::Socket;
+use yanet_muxer::{Error, Muxer};
+use yanet_sim::{Network, SimSocket};
+
+fn within<T>(future: impl Future<Output = T>) -> T {
+    let timeout = async {
+        futures_timer::Delay::new(Duration::from_secs(2)).await;
+        panic!("timed out");
+    };
+    block_on(or(future, timeout))
+}
+
+fn pair() -> (Muxer<SimSocket>, Muxer<SimSocket>, yanet_sim::NodeId) {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let b_id = b.id();
+    (Muxer::new(a), Muxer::new(b), b_id)
+}
+
+#[test]
+fn stream_round_trip() {
+    let (a, b, b_id) = pair();
+    let (a, b) = (a.socket("echo"), b.socket("echo"));
+    within(async {
+        let mut opened = a.open_stream(b_id).unwrap();
+        opened.send(&1u32).await.unwrap();
+        let mut accepted = b.accept().await.unwrap();
+        assert_eq!(accepted.id(), opened.id());
+        assert_eq!(accepted.recv::<u32>().await.unwrap(), 1);
+        accepted.send(&2u32).await.unwrap();
+        assert_eq!(opened.recv::<u32>().await.unwrap(), 2);
+    });
+}
+
+#[test]
+fn streams_to_one_peer_stay_apart() {
+    let (a, b, b_id) = pair();
+    let (a, b) = (a.socket("echo"), b.socket("echo"));
+    within(async {
+        let mut first = a.open_stream(b_id).unwrap();
+        let mut second = a.open_stream(b_id).unwrap();
+        first.send(&1u32).await.unwrap();
+        second.send(&2u32).await.unwrap();
+        let mut x = b.accept().await.unwrap();
+        let mut y = b.accept().await.unwrap();
+        assert_eq!(x.recv::<u32>().await.unwrap(), 1);
+        assert_eq!(y.recv::<u32>().await.unwrap(), 2);
+    });
+}
+
+#[test]
+fn close_ends_the_peer_stream() {
+    let (a, b, b_id) = pair();
+    let (a, b) = (a.socket("echo"), b.socket("echo"));
+    within(async {
+        let mut opened = a.open_stream(b_id).unwrap();
+        opened.send(&1u32).await.unwrap();
+        let mut accepted = b.accept().await.unwrap();
+        assert_eq!(accepted.recv::<u32>().await.unwrap(), 1);
+        opened.close().await.unwrap();
+        let closed = accepted.recv::<u32>().await;
+        assert!(matches!(closed, Err(Error::StreamClosed)));
+    });
+}
+
+#[test]
+fn drop_closes_the_peer_stream() {
+    let (a, b, b_id) = pair();
+    let (mut a, b) = (a.socket("echo"), b.socket("echo"));
+    within(async {
+        let mut opened = a.open_stream(b_id).unwrap();
+        opened.send(&1u32).await.unwrap();
+        let mut accepted = b.accept().await.unwrap();
+        assert_eq!(accepted.recv::<u32>().await.unwrap(), 1);
+        drop(opened);
+        // The Close goes out the next time the dropping side runs its muxer.
+        let pump = async {
+            a.recv::<u32>().await.unwrap();
+            unreachable!("no datagrams are sent");
+        };
+        let closed = or(accepted.recv::<u32>(), pump).await;
+        assert!(matches!(closed, Err(Error::StreamClosed)));
+    });
+}
+
+#[test]
+fn unread_stream_does_not_block_other_services() {
+    let (a, b, b_id) = pair();
+    let (streams, mut datagrams) = (a.socket("stream"), a.socket("datagram"));
+    let (_unread, mut service) = (b.socket("stream"), b.socket("datagram"));
+    within(async {
+        let mut opened = streams.open_stream(b_id).unwrap();
+        for n in 0..32u32 {
+            opened.send(&n).await.unwrap();
+        }
+        datagrams.send(&7u32, b_id).await.unwrap();
+        assert_eq!(service.recv::<u32>().await.unwrap().0, 7);
+    });
+}
+
+#[test]
+fn refuses_streams_beyond_the_accept_queue() {
+    let (a, b, b_id) = pair();
+    let (a, mut b) = (a.socket("echo"), b.socket("echo"));
+    within(async {
+        let mut opened = Vec::new();
+        for n in 0..12u32 {
+            let mut stream = a.open_stream(b_id).unwrap();
+            stream.send(&n).await.unwrap();
+            opened.push(stream);
+        }
+        // Dispatch everything on the accepting side without accepting.
+        let pump = async {
+            b.recv::<u32>().await.unwrap();
+            unreachable!("no datagrams are sent");
+        };
+        let last = opened.last_mut().unwrap();
+        let refused = or(last.recv::<u32>(), pump).await;
+        assert!(matches!(refused, Err(Error::StreamClosed)));
+    });
+}
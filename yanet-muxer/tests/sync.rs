@@ -0,0 +1,13 @@
+#![cfg(feature = "sync")]
+
+use yanet_muxer::{Muxer, MuxerSocket, MuxerStream};
+use yanet_udp::Udp;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn handles_are_send_and_sync() {
+    assert_send_sync::<Muxer<Udp>>();
+    assert_send_sync::<MuxerSocket<Udp>>();
+    assert_send_sync::<MuxerStream<Udp>>();
+}
@@ -4,10 +4,12 @@
 use serde::{Deserialize, Serialize};
 use snow::{HandshakeState, TransportState};
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::Debug,
 };
-use yanet_core::Socket;
+use yanet_core::{BroadcastError, Lenient, Socket};
+
+const TAG_LEN: usize = 16;
 
 #[derive(Default, Debug)]
 pub enum NoiseSession {
@@ -33,12 +35,14 @@ pub enum Error<E> {
     Noise(snow::Error),
     Io(E),
     Serde(postcard::Error),
+    Broadcast(BroadcastError<[u8; 32], Error<E>>),
 }
 
 pub struct NoiseSocket<S: Socket> {
     private_key: [u8; 32],
     socket: S,
     sessions: BTreeMap<S::Addr, NoiseSession>,
+    outgoing: VecDeque<(Msg, S::Addr)>,
 }
 
 impl<S: Socket> NoiseSocket<S> {
@@ -47,6 +51,7 @@ impl<S: Socket> NoiseSocket<S> {
             private_key: p,
             socket,
             sessions: Default::default(),
+            outgoing: VecDeque::new(),
         }
     }
     pub async fn advertise(&mut self) -> Result<(), Error<S::Error>> {
@@ -54,6 +59,23 @@ impl<S: Socket> NoiseSocket<S> {
     }
 }
 
+impl<S> NoiseSocket<S>
+where
+    S: Socket,
+    S::Addr: Clone,
+{
+    // Handshake replies are queued here instead of held across an await, so a
+    // cancelled recv still sends them, and before any payload that follows.
+    async fn flush(&mut self) -> Result<(), Error<S::Error>> {
+        while let Some((msg, addr)) = self.outgoing.front() {
+            let sent = self.socket.send(msg, addr.clone()).await;
+            self.outgoing.pop_front();
+            sent.map_err(Error::Io)?;
+        }
+        Ok(())
+    }
+}
+
 impl<S> Socket for NoiseSocket<S>
 where
     S: Socket,
@@ -69,38 +91,48 @@ where
     {
         let addrs: BTreeSet<[u8; 32]> = self
             .sessions
-            .iter()
-            .filter_map(|(_a, s)| s.get_remote_static())
+            .values()
+            .filter_map(|s| s.get_remote_static())
             .collect();
+        let mut failures = Vec::new();
         for addr in addrs {
-            self.send(data, addr).await?;
+            if let Err(err) = self.send(data, addr).await {
+                yanet_core::debug!(key = ?addr, error = ?err, "broadcast to peer failed");
+                failures.push((addr, err));
+            }
         }
-        Ok(())
+        BroadcastError::check(failures).map_err(Error::Broadcast)
     }
 
     async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
     where
         D: Serialize + ?Sized,
     {
+        self.flush().await?;
         let ret = self
             .sessions
             .iter_mut()
             .find_map(|(_a, s)| match s {
                 NoiseSession::Transport(t) if t.get_remote_static() == Some(addr.as_slice()) => {
-                    return Some((_a, t))
+                    Some((_a, t))
                 }
                 _ => None,
             })
             .map(|(a, t)| -> Result<_, Error<S::Error>> {
-                let mut buf = [0u8; 1024];
-                let buf = postcard::to_slice(data, &mut buf).map_err(Error::Serde)?;
-                let mut write_buf = [0u8; 1024];
+                let buf = postcard::to_allocvec(data).map_err(Error::Serde)?;
+                let mut write_buf = vec![0u8; buf.len() + TAG_LEN];
                 let nonce = t.sending_nonce();
-                let len = t.write_message(buf, &mut write_buf).map_err(Error::Noise)?;
+                let len = t
+                    .write_message(&buf, &mut write_buf)
+                    .map_err(Error::Noise)?;
                 let msg = Msg::Payload(nonce, write_buf[..len].to_vec());
                 Ok((a.clone(), msg))
             })
             .transpose()?;
+        match &ret {
+            Some((a, _)) => yanet_core::trace!(peer = ?a, "sending payload"),
+            None => yanet_core::debug!(key = ?addr, "no session for key, dropping payload"),
+        }
         if let Some((a, m)) = ret {
             self.socket.send(&m, a).await.map_err(Error::Io)?;
         }
@@ -112,75 +144,113 @@ where
         D: serde::de::DeserializeOwned,
     {
         let mut hs_buf = [0u8; 128];
-        let mut send_first: Option<(Msg, S::Addr)> = None;
 
         loop {
-            if let Some((data, addr)) = send_first.take() {
-                self.socket.send(&data, addr).await.map_err(Error::Io)?;
-            }
+            self.flush().await?;
 
-            let (msg, addr) = self.socket.recv::<Msg>().await.map_err(Error::Io)?;
+            let (Lenient(msg), addr) = self
+                .socket
+                .recv::<Lenient<Msg>>()
+                .await
+                .map_err(Error::Io)?;
+            let Some(msg) = msg else {
+                yanet_core::debug!(peer = ?addr, "dropping unreadable message");
+                continue;
+            };
             let entry = self.sessions.entry(addr.clone()).or_default();
             match (core::mem::take(entry), msg) {
                 (NoiseSession::Initiating, Msg::Hello) => {
+                    yanet_core::debug!(peer = ?addr, "hello received, initiating handshake");
                     let mut hs = Box::new(builder(true, self.private_key));
                     let len = hs.write_message(&[], &mut hs_buf).map_err(Error::Noise)?;
-                    send_first = Some((Msg::XX1(hs_buf[..len].to_vec()), addr));
+                    self.outgoing
+                        .push_back((Msg::XX1(hs_buf[..len].to_vec()), addr));
                     let mut xx1 = [0u8; 32];
                     xx1.copy_from_slice(&hs_buf[..32]);
                     *entry = NoiseSession::XX1Sent(xx1, hs);
                 }
                 (NoiseSession::XX1Sent(xx1, _), Msg::XX1(vec)) => {
                     if vec.as_slice() > xx1.as_slice() {
+                        yanet_core::debug!(peer = ?addr, "simultaneous initiation, responding");
                         let mut hs = Box::new(builder(false, self.private_key));
-                        hs.read_message(vec.as_slice(), &mut hs_buf)
-                            .map_err(Error::Noise)?;
+                        if let Err(err) = hs.read_message(vec.as_slice(), &mut hs_buf) {
+                            yanet_core::debug!(peer = ?addr, error = ?err, "dropping bad handshake");
+                            continue;
+                        }
                         let len = hs.write_message(&[], &mut hs_buf).map_err(Error::Noise)?;
-                        send_first = Some((Msg::XX2(hs_buf[..len].to_vec()), addr));
+                        self.outgoing
+                            .push_back((Msg::XX2(hs_buf[..len].to_vec()), addr));
                         *entry = NoiseSession::XX2Sent(hs);
                     }
                 }
                 (NoiseSession::XX1Sent(_, mut hs), Msg::XX2(vec)) => {
                     // <- 2
-                    hs.read_message(&vec, &mut hs_buf).map_err(Error::Noise)?;
+                    if let Err(err) = hs.read_message(&vec, &mut hs_buf) {
+                        yanet_core::debug!(peer = ?addr, error = ?err, "dropping bad handshake");
+                        continue;
+                    }
                     // -> 3
                     let len = hs.write_message(&[], &mut hs_buf).map_err(Error::Noise)?;
-                    send_first = Some((Msg::XX3(hs_buf[..len].to_vec()), addr));
                     let transport = hs.into_transport_mode().map_err(Error::Noise)?;
+                    yanet_core::debug!(peer = ?addr, "handshake complete as initiator");
+                    self.outgoing
+                        .push_back((Msg::XX3(hs_buf[..len].to_vec()), addr));
                     *entry = NoiseSession::Transport(transport);
                 }
                 (NoiseSession::XX2Sent(mut hs), Msg::XX3(msg)) => {
-                    hs.read_message(&msg, &mut hs_buf).map_err(Error::Noise)?;
+                    if let Err(err) = hs.read_message(&msg, &mut hs_buf) {
+                        yanet_core::debug!(peer = ?addr, error = ?err, "dropping bad handshake");
+                        continue;
+                    }
                     let transport = hs.into_transport_mode().map_err(Error::Noise)?;
+                    yanet_core::debug!(peer = ?addr, "handshake complete as responder");
                     *entry = NoiseSession::Transport(transport);
                 }
                 (NoiseSession::Transport(mut t), Msg::Payload(nonce, msg)) => {
-                    if nonce >= t.receiving_nonce() {
+                    let expected = t.receiving_nonce();
+                    if nonce >= expected {
                         t.set_receiving_nonce(nonce);
                     }
-                    let len = t.read_message(&msg, &mut hs_buf).map_err(Error::Noise)?;
+                    let mut buf = vec![0u8; msg.len()];
+                    let read = t.read_message(&msg, &mut buf);
+                    if read.is_err() {
+                        t.set_receiving_nonce(expected);
+                    }
                     *entry = NoiseSession::Transport(t);
-                    let ret = postcard::from_bytes(&hs_buf[..len]).map_err(Error::Serde)?;
+                    let Ok(len) = read else {
+                        yanet_core::debug!(peer = ?addr, nonce, "dropping undecryptable payload");
+                        continue;
+                    };
+                    yanet_core::trace!(peer = ?addr, nonce, "received payload");
+                    let ret = postcard::from_bytes(&buf[..len]).map_err(Error::Serde)?;
                     return Ok((ret, entry.get_remote_static().unwrap()));
                 }
 
                 (_, Msg::XX1(msg)) => {
+                    yanet_core::debug!(peer = ?addr, "handshake requested, responding");
                     let mut hs = Box::new(builder(false, self.private_key));
-                    hs.read_message(msg.as_slice(), &mut hs_buf)
-                        .map_err(Error::Noise)?;
+                    if let Err(err) = hs.read_message(msg.as_slice(), &mut hs_buf) {
+                        yanet_core::debug!(peer = ?addr, error = ?err, "dropping bad handshake");
+                        continue;
+                    }
                     let len = hs.write_message(&[], &mut hs_buf).map_err(Error::Noise)?;
-                    send_first = Some((Msg::XX2(hs_buf[..len].to_vec()), addr));
+                    self.outgoing
+                        .push_back((Msg::XX2(hs_buf[..len].to_vec()), addr));
                     *entry = NoiseSession::XX2Sent(hs);
                 }
                 (_, Msg::Payload(_, _)) => {
+                    yanet_core::debug!(peer = ?addr, "payload without session, re-initiating");
                     let mut hs = Box::new(builder(true, self.private_key));
                     let len = hs.write_message(&[], &mut hs_buf).map_err(Error::Noise)?;
-                    send_first = Some((Msg::XX1(hs_buf[..len].to_vec()), addr));
+                    self.outgoing
+                        .push_back((Msg::XX1(hs_buf[..len].to_vec()), addr));
                     let mut xx1 = [0u8; 32];
                     xx1.copy_from_slice(&hs_buf[..32]);
                     *entry = NoiseSession::XX1Sent(xx1, hs);
                 }
-                _ => {}
+                _ => {
+                    yanet_core::debug!(peer = ?addr, "unexpected handshake message");
+                }
             }
         }
     }
@@ -0,0 +1,84 @@
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use async_channel::{bounded, Sender};
+use serde::de::DeserializeOwned;
+use yanet_core::{Lenient, Socket};
+
+use crate::Pair;
+
+pub enum Wake<M, A, E, C> {
+    Timer,
+    Recv(Result<(M, A), E>),
+    Command(C),
+}
+
+pub struct Commands<C> {
+    pair: Pair<C>,
+}
+
+impl<C> Clone for Commands<C> {
+    fn clone(&self) -> Self {
+        Self {
+            pair: self.pair.clone(),
+        }
+    }
+}
+
+impl<C> Commands<C> {
+    pub fn new(cap: usize) -> Self {
+        Self { pair: bounded(cap) }
+    }
+
+    /// Gives up after `limit` so a caller cannot hang on a service that is not running.
+    pub async fn call<R>(
+        &self,
+        limit: Duration,
+        command: impl FnOnce(Sender<R>) -> C,
+    ) -> Option<R> {
+        let (reply, rx) = bounded(1);
+        let call = async {
+            self.pair.0.send(command(reply)).await.ok()?;
+            rx.recv().await.ok()
+        };
+        let timeout = async {
+            futures_timer::Delay::new(limit).await;
+            yanet_core::debug!("command not answered by service");
+            None
+        };
+        futures_micro::or!(call, timeout).await
+    }
+
+    pub async fn next<S, M>(&self, socket: &mut S, wake: Instant) -> Wake<M, S::Addr, S::Error, C>
+    where
+        S: Socket,
+        S::Addr: Debug,
+        M: DeserializeOwned,
+    {
+        let sleep = async {
+            futures_timer::Delay::new(wake.saturating_duration_since(Instant::now())).await;
+            Wake::Timer
+        };
+        // Undecodable datagrams are skipped; socket errors are for the service to handle.
+        let recv = async {
+            loop {
+                match socket.recv::<Lenient<M>>().await {
+                    Ok((Lenient(Some(msg)), addr)) => return Wake::Recv(Ok((msg, addr))),
+                    Ok((Lenient(None), addr)) => {
+                        yanet_core::debug!(peer = ?addr, "dropping unreadable message");
+                    }
+                    Err(err) => return Wake::Recv(Err(err)),
+                }
+            }
+        };
+        let command = async {
+            match self.pair.1.recv().await {
+                Ok(command) => Wake::Command(command),
+                Err(_) => std::future::pending().await,
+            }
+        };
+        futures_micro::or!(sleep, recv, command).await
+    }
+}
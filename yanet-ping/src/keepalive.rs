@@ -0,0 +1,237 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use async_channel::{bounded, Receiver};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use yanet_core::Socket;
+
+use crate::{Event, Pair};
+
+#[derive(Serialize)]
+enum FrameRef<'a, D: ?Sized> {
+    Data(&'a D),
+    Ping,
+    Pong,
+}
+
+#[derive(Deserialize)]
+enum Frame<D> {
+    Data(D),
+    Ping,
+    Pong,
+}
+
+struct Peer {
+    last_sent: Instant,
+    last_recv: Instant,
+    up: bool,
+}
+
+impl Peer {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            last_sent: now,
+            last_recv: now,
+            up: false,
+        }
+    }
+}
+
+pub struct KeepAlive<S: Socket> {
+    inner: S,
+    interval: Duration,
+    misses: u32,
+    give_up: Duration,
+    peers: BTreeMap<S::Addr, Peer>,
+    pongs: VecDeque<S::Addr>,
+    events: Pair<Event<S::Addr>>,
+}
+
+impl<S> KeepAlive<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug,
+    S::Error: Debug,
+{
+    pub fn new(inner: S, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            misses: 3,
+            give_up: Duration::from_secs(300),
+            peers: BTreeMap::new(),
+            pongs: VecDeque::new(),
+            events: bounded(64),
+        }
+    }
+
+    pub fn misses(mut self, misses: u32) -> Self {
+        self.misses = misses.max(1);
+        self
+    }
+
+    // Peers down for this long are no longer probed, until they are heard from again.
+    pub fn give_up_after(mut self, after: Duration) -> Self {
+        self.give_up = after;
+        self
+    }
+
+    pub fn add_peer(&mut self, addr: S::Addr) {
+        self.peers.entry(addr).or_insert_with(Peer::new);
+    }
+
+    pub fn remove_peer(&mut self, addr: &S::Addr) -> bool {
+        self.peers.remove(addr).is_some()
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = (&S::Addr, bool)> {
+        self.peers.iter().map(|(addr, peer)| (addr, peer.up))
+    }
+
+    pub fn is_up(&self, addr: &S::Addr) -> bool {
+        self.peers.get(addr).is_some_and(|peer| peer.up)
+    }
+
+    pub fn events(&self) -> Receiver<Event<S::Addr>> {
+        self.events.1.clone()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn dead_after(&self) -> Duration {
+        self.interval * self.misses
+    }
+
+    fn sent(&mut self, addr: &S::Addr) {
+        if let Some(peer) = self.peers.get_mut(addr) {
+            peer.last_sent = Instant::now();
+        }
+    }
+
+    fn received(&mut self, addr: &S::Addr) {
+        let Some(peer) = self.peers.get_mut(addr) else {
+            return;
+        };
+        peer.last_recv = Instant::now();
+        if !peer.up {
+            peer.up = true;
+            yanet_core::debug!(peer = ?addr, "peer is up");
+            self.events.0.try_send(Event::Up(addr.clone())).ok();
+        }
+    }
+
+    // Replies wait here rather than across an await in recv, so cancelling
+    // recv does not lose them.
+    async fn reply(&mut self) {
+        while let Some(addr) = self.pongs.front().cloned() {
+            if let Err(err) = self.inner.send(&FrameRef::<()>::Pong, addr.clone()).await {
+                yanet_core::debug!(peer = ?addr, error = ?err, "keepalive reply failed");
+            }
+            self.pongs.pop_front();
+            self.sent(&addr);
+        }
+    }
+
+    async fn maintain(&mut self) -> Instant {
+        let now = Instant::now();
+        let dead_after = self.dead_after();
+        let give_up = dead_after + self.give_up;
+        let mut idle = Vec::new();
+        for (addr, peer) in self.peers.iter_mut() {
+            if peer.up && now.duration_since(peer.last_recv) >= dead_after {
+                peer.up = false;
+                yanet_core::debug!(peer = ?addr, "peer is down");
+                self.events.0.try_send(Event::Down(addr.clone())).ok();
+            }
+            if now.duration_since(peer.last_recv) >= give_up {
+                continue;
+            }
+            if now.duration_since(peer.last_sent) >= self.interval {
+                idle.push(addr.clone());
+            }
+        }
+        for addr in idle {
+            yanet_core::trace!(peer = ?addr, "sending keepalive");
+            if let Err(err) = self.inner.send(&FrameRef::<()>::Ping, addr.clone()).await {
+                yanet_core::debug!(peer = ?addr, error = ?err, "keepalive failed");
+            }
+            self.sent(&addr);
+        }
+        self.peers
+            .values()
+            .filter(|peer| now.duration_since(peer.last_recv) < give_up)
+            .map(|peer| {
+                let next = peer.last_sent + self.interval;
+                if peer.up {
+                    next.min(peer.last_recv + dead_after)
+                } else {
+                    next
+                }
+            })
+            .min()
+            .unwrap_or(now + self.interval)
+    }
+}
+
+impl<S> Socket for KeepAlive<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug,
+    S::Error: Debug,
+{
+    type Addr = S::Addr;
+    type Error = S::Error;
+
+    async fn broadcast<D>(&mut self, data: &D) -> Result<(), Self::Error>
+    where
+        D: Serialize,
+    {
+        let ret = self.inner.broadcast(&FrameRef::Data(data)).await;
+        let now = Instant::now();
+        for peer in self.peers.values_mut() {
+            peer.last_sent = now;
+        }
+        self.maintain().await;
+        ret
+    }
+
+    async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
+    where
+        D: Serialize + ?Sized,
+    {
+        self.inner.send(&FrameRef::Data(data), addr.clone()).await?;
+        self.sent(&addr);
+        self.maintain().await;
+        Ok(())
+    }
+
+    async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
+    where
+        D: DeserializeOwned,
+    {
+        loop {
+            self.reply().await;
+            let wake = self.maintain().await;
+            let sleep = async {
+                futures_timer::Delay::new(wake.saturating_duration_since(Instant::now())).await;
+                None
+            };
+            let recv = async { Some(self.inner.recv::<Frame<D>>().await) };
+            let Some((frame, addr)) = futures_micro::or!(sleep, recv).await.transpose()? else {
+                continue;
+            };
+            self.received(&addr);
+            match frame {
+                Frame::Data(data) => return Ok((data, addr)),
+                Frame::Ping => self.pongs.push_back(addr),
+                Frame::Pong => {}
+            }
+        }
+    }
+}
@@ -1,29 +1,176 @@
 #![allow(incomplete_features)]
 #![feature(async_fn_in_trait)]
-use std::time::{Duration, Instant};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
+use async_channel::{bounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
 use yanet_core::{Service, ServiceName, Socket};
 
-pub struct Pinger {
+mod commands;
+mod keepalive;
+mod pmtu;
+mod probe;
+mod stats;
+pub use commands::{Commands, Wake};
+pub use keepalive::KeepAlive;
+pub use pmtu::Pmtu;
+pub use stats::PeerStats;
+
+use probe::{Active, Command};
+
+type Pair<T> = (Sender<T>, Receiver<T>);
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Probe {
+    Ping(u32, u64),
+    Pong(u32, u64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event<A> {
+    Up(A),
+    Down(A),
+    Rtt(A, Duration),
+}
+
+pub struct Pinger<A> {
     dur: Duration,
+    dead_after: Duration,
+    probe_interval: Duration,
+    probe_timeout: Duration,
+    epoch: Instant,
+    peers: Arc<Mutex<BTreeMap<A, PeerStats>>>,
+    events: Pair<Event<A>>,
+    commands: Commands<Command<A>>,
+}
+
+impl<A> Clone for Pinger<A> {
+    fn clone(&self) -> Self {
+        Self {
+            dur: self.dur,
+            dead_after: self.dead_after,
+            probe_interval: self.probe_interval,
+            probe_timeout: self.probe_timeout,
+            epoch: self.epoch,
+            peers: self.peers.clone(),
+            events: self.events.clone(),
+            commands: self.commands.clone(),
+        }
+    }
 }
 
-impl Pinger {
+impl<A: Ord + Clone> Pinger<A> {
     pub fn new(dur: Duration) -> Self {
-        Self { dur }
+        Self {
+            dur,
+            dead_after: dur * 3,
+            probe_interval: Duration::from_millis(200),
+            probe_timeout: Duration::from_secs(1),
+            epoch: Instant::now(),
+            peers: Default::default(),
+            events: bounded(64),
+            commands: Commands::new(16),
+        }
+    }
+
+    pub fn dead_after(mut self, dead_after: Duration) -> Self {
+        self.dead_after = dead_after;
+        self
+    }
+
+    pub fn probe_interval(mut self, interval: Duration) -> Self {
+        self.probe_interval = interval;
+        self
+    }
+
+    pub fn probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    pub async fn ping(&self, addr: A, count: u32) -> Option<PeerStats> {
+        let limit = self.probe_interval * count + self.probe_timeout * 2;
+        let probe = |reply| Command { addr, count, reply };
+        self.commands.call(limit, probe).await
+    }
+
+    pub fn peers(&self) -> Vec<(A, PeerStats)> {
+        let peers = self.peers.lock().unwrap();
+        peers.iter().map(|(a, s)| (a.clone(), s.clone())).collect()
+    }
+
+    pub fn peer(&self, addr: &A) -> Option<PeerStats> {
+        self.peers.lock().unwrap().get(addr).cloned()
+    }
+
+    pub fn alive(&self) -> Vec<A> {
+        let peers = self.peers.lock().unwrap();
+        peers
+            .iter()
+            .filter(|(_, s)| s.alive)
+            .map(|(a, _)| a.clone())
+            .collect()
+    }
+
+    pub fn events(&self) -> Receiver<Event<A>> {
+        self.events.1.clone()
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_micros() as u64
+    }
+
+    fn emit(&self, event: Event<A>) {
+        self.events.0.try_send(event).ok();
+    }
+
+    fn seen(&self, addr: &A) {
+        let mut peers = self.peers.lock().unwrap();
+        let stats = peers.entry(addr.clone()).or_insert_with(PeerStats::new);
+        stats.last_seen = Instant::now();
+        if !stats.alive {
+            stats.alive = true;
+            self.emit(Event::Up(addr.clone()));
+        }
+    }
+
+    fn record(&self, addr: &A, rtt: Duration) {
+        self.seen(addr);
+        if let Some(stats) = self.peers.lock().unwrap().get_mut(addr) {
+            stats.record(rtt);
+        }
+        self.emit(Event::Rtt(addr.clone(), rtt));
+    }
+
+    fn tick(&self) {
+        let mut peers = self.peers.lock().unwrap();
+        for (addr, stats) in peers.iter_mut() {
+            if stats.alive && stats.last_seen.elapsed() > self.dead_after {
+                stats.alive = false;
+                self.emit(Event::Down(addr.clone()));
+            }
+            stats.sent += 1;
+        }
     }
 }
-impl ServiceName for Pinger {
+
+impl<A> ServiceName for Pinger<A> {
     type Name = &'static str;
 
     fn name(&self) -> Self::Name {
         "pinger"
     }
 }
-impl<S> Service<S> for Pinger
+impl<S> Service<S> for Pinger<S::Addr>
 where
     S: Socket,
-    S::Addr: std::fmt::Debug,
+    S::Addr: Ord + Clone + Debug,
+    S::Error: Debug,
 {
     type Output = ();
 
@@ -31,18 +178,59 @@ where
 
     async fn upgrade(&self, mut socket: S) -> Result<Self::Output, Self::Error> {
         let mut start = Instant::now();
+        let mut seq = 0u32;
+        let mut active: Vec<Active<S::Addr>> = Vec::new();
         loop {
+            let now = Instant::now();
             if start.elapsed() > self.dur {
-                socket.broadcast(&"hello").await?;
+                self.tick();
+                seq = seq.wrapping_add(1);
+                yanet_core::debug!(seq, "broadcasting ping");
+                if let Err(err) = socket.broadcast(&Probe::Ping(seq, self.now())).await {
+                    yanet_core::warn!(error = ?err, "ping broadcast failed");
+                }
                 start = Instant::now();
             }
-            let sleep = async {
-                futures_timer::Delay::new(self.dur - start.elapsed()).await;
-                None
-            };
-            let recv = async { Some(socket.recv::<String>().await) };
-            if let Some((s, a)) = futures_micro::or!(sleep, recv).await.transpose()? {
-                println!("received {} from {:?}", s, a);
+            for probe in active.iter_mut().filter(|probe| probe.due(now)) {
+                seq = seq.wrapping_add(1);
+                probe.sent(seq, self.probe_interval, self.probe_timeout);
+                let ping = Probe::Ping(seq, self.now());
+                if let Err(err) = socket.send(&ping, probe.addr.clone()).await {
+                    yanet_core::debug!(peer = ?probe.addr, error = ?err, "probe failed");
+                }
+            }
+            active.retain(|probe| !probe.finish(now));
+            let wake = active
+                .iter()
+                .map(Active::wake)
+                .fold(start + self.dur, Instant::min);
+            match self.commands.next::<S, Probe>(&mut socket, wake).await {
+                Wake::Recv(Ok((Probe::Ping(seq, sent), addr))) => {
+                    yanet_core::trace!(peer = ?addr, seq, "ping received");
+                    self.seen(&addr);
+                    if let Err(err) = socket.send(&Probe::Pong(seq, sent), addr.clone()).await {
+                        yanet_core::debug!(peer = ?addr, error = ?err, "pong failed");
+                    }
+                }
+                Wake::Recv(Ok((Probe::Pong(seq, sent), addr))) => {
+                    let rtt = Duration::from_micros(self.now().saturating_sub(sent));
+                    yanet_core::debug!(peer = ?addr, seq, ?rtt, "pong received");
+                    let mut probed = false;
+                    for probe in active.iter_mut().filter(|probe| probe.addr == addr) {
+                        probed |= probe.answer(seq, rtt);
+                    }
+                    if probed {
+                        self.seen(&addr);
+                    } else {
+                        self.record(&addr, rtt);
+                    }
+                }
+                Wake::Recv(Err(err)) => return Err(err),
+                Wake::Command(command) => {
+                    yanet_core::debug!(peer = ?command.addr, count = command.count, "probing peer");
+                    active.push(Active::new(command));
+                }
+                Wake::Timer => {}
             }
         }
     }
@@ -0,0 +1,239 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_channel::Sender;
+use serde::{Deserialize, Serialize};
+use yanet_core::{Service, ServiceName, Socket};
+
+use crate::{Commands, Wake};
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Msg {
+    Probe(u32, Vec<u8>),
+    Ack(u32),
+}
+
+struct Search<A> {
+    addr: A,
+    min: usize,
+    good: usize,
+    bad: usize,
+    size: usize,
+    id: Option<u32>,
+    deadline: Instant,
+    tries: u32,
+    reply: Sender<Option<usize>>,
+}
+
+impl<A> Search<A> {
+    fn new(addr: A, min: usize, max: usize, reply: Sender<Option<usize>>) -> Self {
+        Self {
+            addr,
+            min,
+            good: min - 1,
+            bad: max + 1,
+            size: max,
+            id: None,
+            deadline: Instant::now(),
+            tries: 0,
+            reply,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.bad - self.good <= 1
+    }
+
+    fn advance(&mut self, ok: bool) {
+        if ok {
+            self.good = self.size;
+        } else {
+            self.bad = self.size;
+        }
+        self.size = (self.good + self.bad) / 2;
+        self.id = None;
+        self.tries = 0;
+    }
+
+    fn result(&self) -> Option<usize> {
+        (self.good >= self.min).then_some(self.good)
+    }
+}
+
+pub struct Pmtu<A> {
+    min: usize,
+    max: usize,
+    timeout: Duration,
+    attempts: u32,
+    results: Arc<Mutex<BTreeMap<A, usize>>>,
+    commands: Commands<(A, Sender<Option<usize>>)>,
+}
+
+impl<A> Clone for Pmtu<A> {
+    fn clone(&self) -> Self {
+        Self {
+            min: self.min,
+            max: self.max,
+            timeout: self.timeout,
+            attempts: self.attempts,
+            results: self.results.clone(),
+            commands: self.commands.clone(),
+        }
+    }
+}
+
+impl<A: Ord + Clone> Pmtu<A> {
+    pub fn new() -> Self {
+        Self {
+            min: 64,
+            max: 9000,
+            timeout: Duration::from_millis(500),
+            attempts: 2,
+            results: Default::default(),
+            commands: Commands::new(16),
+        }
+    }
+
+    pub fn range(mut self, min: usize, max: usize) -> Self {
+        self.min = min.max(1);
+        self.max = max.max(self.min);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Finds the largest probe payload that reaches `addr`. This is the size of
+    /// the data handed to the socket, not of the datagram on the wire, which
+    /// also carries the serialization and any encryption overhead.
+    pub async fn discover(&self, addr: A) -> Option<usize> {
+        // Every step of the search may use all attempts before the size is ruled out.
+        let steps = usize::BITS - (self.max - self.min + 1).leading_zeros() + 1;
+        let limit = self.timeout * self.attempts * steps;
+        self.commands
+            .call(limit, |reply| (addr, reply))
+            .await
+            .flatten()
+    }
+
+    pub fn mtu(&self, addr: &A) -> Option<usize> {
+        self.results.lock().unwrap().get(addr).copied()
+    }
+
+    pub fn results(&self) -> Vec<(A, usize)> {
+        let results = self.results.lock().unwrap();
+        results.iter().map(|(a, mtu)| (a.clone(), *mtu)).collect()
+    }
+
+    fn finish(&self, search: &Search<A>) -> bool {
+        if !search.done() {
+            return false;
+        }
+        let result = search.result();
+        let mut results = self.results.lock().unwrap();
+        match result {
+            Some(mtu) => results.insert(search.addr.clone(), mtu),
+            None => results.remove(&search.addr),
+        };
+        search.reply.try_send(result).ok();
+        true
+    }
+}
+
+impl<A: Ord + Clone> Default for Pmtu<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A> ServiceName for Pmtu<A> {
+    type Name = &'static str;
+
+    fn name(&self) -> Self::Name {
+        "pmtu"
+    }
+}
+
+impl<S> Service<S> for Pmtu<S::Addr>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug,
+    S::Error: Debug,
+{
+    type Output = ();
+
+    type Error = S::Error;
+
+    async fn upgrade(&self, mut socket: S) -> Result<Self::Output, Self::Error> {
+        let mut id = 0u32;
+        let mut searches: Vec<Search<S::Addr>> = Vec::new();
+        loop {
+            let now = Instant::now();
+            for search in searches.iter_mut() {
+                if search.id.is_some() && search.deadline <= now {
+                    search.tries += 1;
+                    if search.tries >= self.attempts {
+                        yanet_core::trace!(peer = ?search.addr, size = search.size, "probe lost");
+                        search.advance(false);
+                    } else {
+                        search.id = None;
+                    }
+                }
+                while !search.done() && search.id.is_none() {
+                    id = id.wrapping_add(1);
+                    let probe = Msg::Probe(id, vec![0; search.size]);
+                    match socket.send(&probe, search.addr.clone()).await {
+                        Ok(()) => {
+                            search.id = Some(id);
+                            search.deadline = Instant::now() + self.timeout;
+                        }
+                        Err(err) => {
+                            let (peer, size) = (&search.addr, search.size);
+                            yanet_core::trace!(peer = ?peer, size, error = ?err, "probe rejected");
+                            search.advance(false);
+                        }
+                    }
+                }
+            }
+            searches.retain(|search| !self.finish(search));
+            let wake = searches
+                .iter()
+                .map(|search| search.deadline)
+                .min()
+                .unwrap_or(now + self.timeout);
+            match self.commands.next::<S, Msg>(&mut socket, wake).await {
+                Wake::Recv(Ok((Msg::Probe(id, padding), addr))) => {
+                    yanet_core::trace!(peer = ?addr, size = padding.len(), "probe received");
+                    if let Err(err) = socket.send(&Msg::Ack(id), addr).await {
+                        yanet_core::debug!(error = ?err, "probe ack failed");
+                    }
+                }
+                Wake::Recv(Ok((Msg::Ack(id), addr))) => {
+                    for search in searches.iter_mut() {
+                        if search.addr == addr && search.id == Some(id) {
+                            yanet_core::trace!(peer = ?addr, size = search.size, "probe acked");
+                            search.advance(true);
+                        }
+                    }
+                }
+                Wake::Recv(Err(err)) => return Err(err),
+                Wake::Command((addr, reply)) => {
+                    yanet_core::debug!(peer = ?addr, "discovering path mtu");
+                    searches.push(Search::new(addr, self.min, self.max, reply));
+                }
+                Wake::Timer => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
+
+use async_channel::Sender;
+
+use crate::PeerStats;
+
+pub(crate) struct Command<A> {
+    pub(crate) addr: A,
+    pub(crate) count: u32,
+    pub(crate) reply: Sender<PeerStats>,
+}
+
+pub(crate) struct Active<A> {
+    pub(crate) addr: A,
+    left: u32,
+    next: Instant,
+    until: Instant,
+    seqs: BTreeSet<u32>,
+    stats: PeerStats,
+    reply: Sender<PeerStats>,
+}
+
+impl<A> Active<A> {
+    pub(crate) fn new(command: Command<A>) -> Self {
+        let now = Instant::now();
+        Self {
+            addr: command.addr,
+            left: command.count,
+            next: now,
+            until: now,
+            seqs: BTreeSet::new(),
+            stats: PeerStats::new(),
+            reply: command.reply,
+        }
+    }
+
+    pub(crate) fn due(&self, now: Instant) -> bool {
+        self.left > 0 && self.next <= now
+    }
+
+    pub(crate) fn sent(&mut self, seq: u32, interval: Duration, timeout: Duration) {
+        let now = Instant::now();
+        self.seqs.insert(seq);
+        self.stats.sent += 1;
+        self.left -= 1;
+        self.next = now + interval;
+        if self.left == 0 {
+            self.until = now + timeout;
+        }
+    }
+
+    pub(crate) fn answer(&mut self, seq: u32, rtt: Duration) -> bool {
+        let answered = self.seqs.remove(&seq);
+        if answered {
+            self.stats.last_seen = Instant::now();
+            self.stats.alive = true;
+            self.stats.record(rtt);
+        }
+        answered
+    }
+
+    pub(crate) fn wake(&self) -> Instant {
+        if self.left > 0 {
+            self.next
+        } else {
+            self.until
+        }
+    }
+
+    pub(crate) fn finish(&self, now: Instant) -> bool {
+        let done = self.left == 0 && (self.seqs.is_empty() || self.until <= now);
+        if done {
+            self.reply.try_send(self.stats.clone()).ok();
+        }
+        done
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub struct PeerStats {
+    pub sent: u64,
+    pub received: u64,
+    pub min: Option<Duration>,
+    pub max: Option<Duration>,
+    pub last: Option<Duration>,
+    pub jitter: Duration,
+    pub last_seen: Instant,
+    pub alive: bool,
+    total: Duration,
+}
+
+impl PeerStats {
+    pub(crate) fn new() -> Self {
+        Self {
+            sent: 0,
+            received: 0,
+            min: None,
+            max: None,
+            last: None,
+            jitter: Duration::ZERO,
+            last_seen: Instant::now(),
+            alive: false,
+            total: Duration::ZERO,
+        }
+    }
+
+    pub fn avg(&self) -> Option<Duration> {
+        (self.received > 0).then(|| self.total / self.received as u32)
+    }
+
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        1.0 - self.received.min(self.sent) as f64 / self.sent as f64
+    }
+
+    pub(crate) fn record(&mut self, rtt: Duration) {
+        if let Some(last) = self.last {
+            // RFC 3550 interarrival jitter estimator.
+            let diff = rtt.abs_diff(last).as_nanos() as i128;
+            let jitter = self.jitter.as_nanos() as i128;
+            let jitter = jitter + (diff - jitter) / 16;
+            self.jitter = Duration::from_nanos(jitter as u64);
+        }
+        self.min = Some(self.min.map_or(rtt, |min| min.min(rtt)));
+        self.max = Some(self.max.map_or(rtt, |max| max.max(rtt)));
+        self.last = Some(rtt);
+        self.total += rtt;
+        self.received += 1;
+    }
+}
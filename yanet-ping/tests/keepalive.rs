@@ -0,0 +1,90 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::Duration;
+
+use futures_lite::future::{block_on, or};
+use yanet_core::Socket;
+use yanet_ping::{Event, KeepAlive};
+use yanet_sim::{Network, SimSocket};
+
+async fn pump<T>(socket: &mut KeepAlive<SimSocket>) -> T {
+    loop {
+        socket.recv::<u32>().await.unwrap();
+    }
+}
+
+async fn sleep(ms: u64) {
+    futures_timer::Delay::new(Duration::from_millis(ms)).await;
+}
+
+#[test]
+fn reports_peers_up_and_down() {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let mut a = KeepAlive::new(a, Duration::from_millis(10));
+    let mut b = KeepAlive::new(b, Duration::from_millis(10));
+    a.add_peer(b_id);
+    b.add_peer(a_id);
+    let events = a.events();
+    block_on(async {
+        let up = or(events.recv(), or(pump(&mut a), pump(&mut b))).await;
+        assert_eq!(up.unwrap(), Event::Up(b_id));
+        assert!(a.is_up(&b_id));
+        net.partition(&[a_id], &[b_id]);
+        let down = or(events.recv(), or(pump(&mut a), pump(&mut b))).await;
+        assert_eq!(down.unwrap(), Event::Down(b_id));
+        assert!(!a.is_up(&b_id));
+    });
+}
+
+#[test]
+fn answers_but_does_not_track_unknown_peers() {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let b_id = b.id();
+    let mut a = KeepAlive::new(a, Duration::from_millis(10));
+    let mut b = KeepAlive::new(b, Duration::from_millis(10));
+    a.add_peer(b_id);
+    let events = a.events();
+    block_on(async {
+        let up = or(events.recv(), or(pump(&mut a), pump(&mut b))).await;
+        assert_eq!(up.unwrap(), Event::Up(b_id));
+    });
+    assert_eq!(b.peers().count(), 0);
+}
+
+#[test]
+fn gives_up_on_silent_peers() {
+    let net = Network::new(1);
+    let (a, silent) = (net.node(), net.node());
+    let mut a = KeepAlive::new(a, Duration::from_millis(10))
+        .misses(1)
+        .give_up_after(Duration::from_millis(30));
+    a.add_peer(silent.id());
+    block_on(or(pump(&mut a), sleep(300)));
+    // About four probes before giving up, rather than one every interval.
+    let sent = net.stats().sent;
+    assert!((2..=8).contains(&sent), "sent {sent}");
+    assert_eq!(a.peers().collect::<Vec<_>>(), vec![(&silent.id(), false)]);
+}
+
+#[test]
+fn resumes_probing_a_peer_that_returns() {
+    let net = Network::new(1);
+    let (a, b) = (net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let mut a = KeepAlive::new(a, Duration::from_millis(10))
+        .misses(1)
+        .give_up_after(Duration::from_millis(20));
+    let mut b = KeepAlive::new(b, Duration::from_millis(10));
+    a.add_peer(b_id);
+    let events = a.events();
+    block_on(async {
+        or(pump(&mut a), sleep(100)).await;
+        b.send(&1u32, a_id).await.unwrap();
+        let up = or(events.recv(), pump(&mut a)).await;
+        assert_eq!(up.unwrap(), Event::Up(b_id));
+    });
+}
@@ -0,0 +1,61 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{sync::mpsc, thread, time::Duration};
+
+use futures_lite::future::{block_on, or};
+use yanet_core::{Service, Socket};
+use yanet_muxer::Muxer;
+use yanet_ping::{Event, Pinger};
+use yanet_sim::{Network, NodeId, SimSocket};
+
+// Runs the test on its own thread so a loop that never yields fails instead of hanging.
+fn watchdog<T: Send + 'static>(test: impl FnOnce() -> T + Send + 'static) -> T {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || tx.send(test()).ok());
+    rx.recv_timeout(Duration::from_secs(5))
+        .expect("test did not finish")
+}
+
+async fn serve<T>(pinger: Pinger<NodeId>, socket: SimSocket) -> T {
+    pinger.upgrade(socket).await.unwrap();
+    unreachable!("pinger exited")
+}
+
+#[test]
+fn measures_rtt_past_unreadable_datagrams() {
+    let net = Network::new(1);
+    let (a, b, mut stray) = (net.node(), net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let pa = Pinger::new(Duration::from_millis(10));
+    let events = pa.events();
+    let rtt = async {
+        stray.send(&u32::MAX, a_id).await.unwrap();
+        stray.send(&u32::MAX, b_id).await.unwrap();
+        loop {
+            if let Event::Rtt(peer, _) = events.recv().await.unwrap() {
+                return peer;
+            }
+        }
+    };
+    let pingers = or(
+        serve(pa.clone(), a),
+        serve(Pinger::new(Duration::from_millis(10)), b),
+    );
+    assert_eq!(block_on(or(rtt, pingers)), b_id);
+    assert!(pa.alive().contains(&b_id));
+}
+
+#[test]
+fn returns_when_socket_closes() {
+    let closed = watchdog(|| {
+        let net = Network::new(1);
+        let muxer = Muxer::new(net.node());
+        let first = muxer.socket("pinger");
+        // Registering the name again closes the first socket's channel.
+        let _second = muxer.socket("pinger");
+        let pinger = Pinger::new(Duration::from_millis(10));
+        block_on(pinger.upgrade(first)).is_err()
+    });
+    assert!(closed);
+}
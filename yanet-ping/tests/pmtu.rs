@@ -0,0 +1,79 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::Duration;
+
+use futures_lite::future::{block_on, or};
+use yanet_muxer::Muxer;
+use yanet_noise::NoiseSocket;
+use yanet_ping::{Event, Pinger, Pmtu};
+use yanet_sim::{Link, Network, SimSocket};
+
+async fn node<T>(
+    socket: SimSocket,
+    key: u8,
+    pinger: Pinger<[u8; 32]>,
+    pmtu: Pmtu<[u8; 32]>,
+    advertise: bool,
+) -> T {
+    let mut noise = NoiseSocket::new([key; 32], socket);
+    if advertise {
+        noise.advertise().await.unwrap();
+    }
+    let muxer = Muxer::new(noise);
+    or(async { muxer.handle(pinger).await.unwrap() }, async {
+        muxer.handle(pmtu).await.unwrap()
+    })
+    .await;
+    unreachable!("services exited")
+}
+
+// Runs Pmtu over Muxer and Noise, learning the peer's key from the first pong.
+fn discover(link: Link, min: usize, max: usize) -> Option<usize> {
+    let net = Network::new(1);
+    net.set_default_link(link);
+    let (a, b) = (net.node(), net.node());
+    let pinger = Pinger::new(Duration::from_millis(10));
+    let pmtu = Pmtu::new()
+        .range(min, max)
+        .timeout(Duration::from_millis(20));
+    let events = pinger.events();
+    let nodes = or(
+        node(a, 1, pinger.clone(), pmtu.clone(), true),
+        node(
+            b,
+            2,
+            Pinger::new(Duration::from_millis(10)),
+            Pmtu::new(),
+            false,
+        ),
+    );
+    let discover = async {
+        let peer = loop {
+            if let Event::Rtt(peer, _) = events.recv().await.unwrap() {
+                break peer;
+            }
+        };
+        pmtu.discover(peer).await
+    };
+    block_on(or(discover, nodes))
+}
+
+#[test]
+fn finds_the_full_range_without_a_limit() {
+    assert_eq!(discover(Link::perfect(), 16, 1000), Some(1000));
+}
+
+#[test]
+fn stays_below_the_link_mtu() {
+    let mtu = discover(Link::perfect().mtu(600), 16, 1000).unwrap();
+    assert!((500..600).contains(&mtu), "{mtu}");
+}
+
+#[test]
+fn gives_up_without_a_service() {
+    let pmtu = Pmtu::<u8>::new()
+        .range(16, 64)
+        .timeout(Duration::from_millis(10));
+    assert_eq!(block_on(pmtu.discover(1)), None);
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use yanet_core::{Service, ServiceName, Socket};
+
+use crate::{Limit, Msg};
+
+// Keeps a relay that grants very short reservations from turning refreshes into a busy loop.
+const MIN_REFRESH: Duration = Duration::from_millis(100);
+
+fn refresh_for(interval: Duration, ttl: u64) -> Duration {
+    interval
+        .min(Duration::from_millis(ttl / 2))
+        .max(MIN_REFRESH)
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    Socket(E),
+    Serde(postcard::Error),
+    Denied(Limit),
+    Timeout,
+}
+
+pub struct RelayClient<A> {
+    relay: A,
+    refresh: Duration,
+    timeout: Duration,
+    attempts: u32,
+}
+
+impl<A: Clone> RelayClient<A> {
+    pub fn new(relay: A) -> Self {
+        Self {
+            relay,
+            refresh: Duration::from_secs(10),
+            timeout: Duration::from_secs(1),
+            attempts: 3,
+        }
+    }
+
+    pub fn refresh(mut self, refresh: Duration) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+}
+
+impl<A> ServiceName for RelayClient<A> {
+    type Name = &'static str;
+
+    fn name(&self) -> Self::Name {
+        "relay"
+    }
+}
+
+impl<S> Service<S> for RelayClient<S::Addr>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug + Serialize + DeserializeOwned,
+    S::Error: Debug,
+{
+    type Output = RelaySocket<S>;
+
+    type Error = Error<S::Error>;
+
+    async fn upgrade(&self, mut socket: S) -> Result<Self::Output, Self::Error> {
+        for _ in 0..self.attempts {
+            yanet_core::debug!(relay = ?self.relay, "reserving relay slot");
+            socket
+                .send(&Msg::<S::Addr>::Reserve, self.relay.clone())
+                .await
+                .map_err(Error::Socket)?;
+            let deadline = Instant::now() + self.timeout;
+            loop {
+                let sleep = async {
+                    futures_timer::Delay::new(deadline.saturating_duration_since(Instant::now()))
+                        .await;
+                    None
+                };
+                let recv = async { Some(socket.recv::<Msg<S::Addr>>().await) };
+                let Some(recv) = futures_micro::or!(sleep, recv).await else {
+                    break;
+                };
+                match recv.map_err(Error::Socket)? {
+                    (Msg::Reserved(ttl), from) if from == self.relay => {
+                        yanet_core::debug!(relay = ?from, ttl, "reservation accepted");
+                        return Ok(RelaySocket {
+                            inner: socket,
+                            relay: self.relay.clone(),
+                            interval: self.refresh,
+                            refresh: refresh_for(self.refresh, ttl),
+                            reserved: Instant::now(),
+                            unreachable: BTreeSet::new(),
+                            denied: 0,
+                        });
+                    }
+                    (Msg::Denied(None, limit), from) if from == self.relay => {
+                        yanet_core::debug!(relay = ?from, ?limit, "reservation denied");
+                        return Err(Error::Denied(limit));
+                    }
+                    (msg, from) => {
+                        yanet_core::trace!(peer = ?from, ?msg, "ignoring message while reserving");
+                    }
+                }
+            }
+        }
+        Err(Error::Timeout)
+    }
+}
+
+pub struct RelaySocket<S: Socket> {
+    inner: S,
+    relay: S::Addr,
+    interval: Duration,
+    refresh: Duration,
+    reserved: Instant,
+    unreachable: BTreeSet<S::Addr>,
+    denied: u64,
+}
+
+impl<S> RelaySocket<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug + Serialize + DeserializeOwned,
+    S::Error: Debug,
+{
+    pub fn relay(&self) -> &S::Addr {
+        &self.relay
+    }
+
+    pub fn is_unreachable(&self, addr: &S::Addr) -> bool {
+        self.unreachable.contains(addr)
+    }
+
+    pub fn denied(&self) -> u64 {
+        self.denied
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    async fn send_relay(&mut self, msg: &Msg<S::Addr>) -> Result<(), Error<S::Error>> {
+        self.inner
+            .send(msg, self.relay.clone())
+            .await
+            .map_err(Error::Socket)
+    }
+
+    async fn reserve(&mut self) -> Result<(), Error<S::Error>> {
+        yanet_core::trace!(relay = ?self.relay, "refreshing reservation");
+        self.reserved = Instant::now();
+        self.send_relay(&Msg::Reserve).await
+    }
+
+    async fn reserve_if_due(&mut self) -> Result<(), Error<S::Error>> {
+        if self.reserved.elapsed() >= self.refresh {
+            self.reserve().await?;
+        }
+        Ok(())
+    }
+}
+
+impl<S> Socket for RelaySocket<S>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug + Serialize + DeserializeOwned,
+    S::Error: Debug,
+{
+    type Addr = S::Addr;
+    type Error = Error<S::Error>;
+
+    async fn broadcast<D>(&mut self, data: &D) -> Result<(), Self::Error>
+    where
+        D: Serialize,
+    {
+        let data = postcard::to_allocvec(data).map_err(Error::Serde)?;
+        self.reserve_if_due().await?;
+        self.send_relay(&Msg::Broadcast(data)).await
+    }
+
+    async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
+    where
+        D: Serialize + ?Sized,
+    {
+        let data = postcard::to_allocvec(data).map_err(Error::Serde)?;
+        yanet_core::trace!(peer = ?addr, len = data.len(), "sending via relay");
+        self.reserve_if_due().await?;
+        self.send_relay(&Msg::Forward(addr, data)).await
+    }
+
+    async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
+    where
+        D: DeserializeOwned,
+    {
+        loop {
+            self.reserve_if_due().await?;
+            let wake = self.reserved + self.refresh;
+            let sleep = async {
+                futures_timer::Delay::new(wake.saturating_duration_since(Instant::now())).await;
+                None
+            };
+            let recv = async { Some(self.inner.recv::<Msg<S::Addr>>().await) };
+            let Some(recv) = futures_micro::or!(sleep, recv).await else {
+                continue;
+            };
+            let (msg, from) = recv.map_err(Error::Socket)?;
+            if from != self.relay {
+                yanet_core::debug!(peer = ?from, "dropping message from unknown relay");
+                continue;
+            }
+            match msg {
+                Msg::Deliver(from, data) => {
+                    yanet_core::trace!(peer = ?from, len = data.len(), "received via relay");
+                    self.unreachable.remove(&from);
+                    let data = postcard::from_bytes(&data).map_err(Error::Serde)?;
+                    return Ok((data, from));
+                }
+                Msg::Unreachable(addr) => {
+                    yanet_core::debug!(peer = ?addr, "peer unreachable via relay");
+                    self.unreachable.insert(addr);
+                }
+                Msg::Reserved(ttl) => {
+                    self.refresh = refresh_for(self.interval, ttl);
+                }
+                Msg::Denied(None, limit) => {
+                    yanet_core::debug!(relay = ?from, ?limit, "reservation denied");
+                    return Err(Error::Denied(limit));
+                }
+                Msg::Denied(Some(addr), limit) => {
+                    yanet_core::debug!(peer = ?addr, ?limit, "denied by relay");
+                    self.denied += 1;
+                }
+                Msg::BroadcastDenied(limit) => {
+                    yanet_core::debug!(?limit, "broadcast denied by relay");
+                    self.denied += 1;
+                }
+                _ => {
+                    yanet_core::debug!("unexpected relay message");
+                }
+            }
+        }
+    }
+}
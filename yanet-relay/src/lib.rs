@@ -1,87 +1,328 @@
 #![allow(incomplete_features)]
 #![feature(async_fn_in_trait)]
 
-//use async_channel::{bounded, unbounded, Receiver, Sender};
-//use std::{cell::RefCell, collections::BTreeMap};
-//use yanet_core::{Authenticated, Channel, Service, ServiceName, Transport};
-//
-//type Pair<T> = (Sender<T>, Receiver<T>);
-//
-//enum RelayMessgage {}
-//
-//pub struct RelayService {
-//    connected_peers: RefCell<BTreeMap<[u8; 32], Vec<Pair<Vec<u8>>>>>,
-//    request_peers: Pair<[u8; 32]>,
-//    incoming: Pair<RelayChannel>,
-//}
-//
-//impl RelayService {
-//    pub fn new() -> Self {
-//        Self {
-//            connected_peers: RefCell::new(BTreeMap::new()),
-//            incoming: unbounded(),
-//            request_peers: bounded(10),
-//        }
-//    }
-//    pub fn request(&self, peer_id: [u8; 32]) -> anyhow::Result<()> {
-//        Ok(self.request_peers.0.try_send(peer_id)?)
-//    }
-//}
-//impl RelayChannel {}
-//
-//pub struct RelayChannel {
-//    tx: Sender<Vec<u8>>,
-//    rx: Receiver<Vec<u8>>,
-//}
-//
-//impl ServiceName for RelayService {
-//    type Name = &'static str;
-//    fn name(&self) -> Self::Name {
-//        "relay"
-//    }
-//}
-//
-//impl<C: Channel + Authenticated> Service<C> for RelayService {
-//    type Output = ();
-//
-//    async fn upgrade(&self, channel: C) -> anyhow::Result<Self::Output> {
-//        let (tx1, rx2) = unbounded();
-//        let (tx2, rx1) = unbounded();
-//
-//        self.connected_peers
-//            .borrow_mut()
-//            .entry(channel.peer_id())
-//            .or_default()
-//            .push((tx2, rx2));
-//        let task1 = async {
-//            while let Ok(value) = channel.recv().await {
-//                tx1.send(value).await.ok();
-//            }
-//            Ok(())
-//        };
-//        let task2 = async {
-//            while let Ok(value) = rx1.recv().await {
-//                channel.send(&value).await?;
-//            }
-//            Ok(()) as anyhow::Result<()>
-//        };
-//        futures_lite::future::or(task1, task2).await?;
-//        Ok(())
-//    }
-//}
-//
-//impl Transport for RelayService {
-//    type Channel = RelayChannel;
-//
-//    async fn get(&self) -> Self::Channel {
-//        loop {
-//            let req = self.request_peers.1.recv().await.unwrap();
-//            if let Some(_) = self.connected_peers.borrow().get(&req) {
-//                continue;
-//            }
-//            break;
-//        }
-//
-//        todo!()
-//    }
-//}
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use yanet_core::{Lenient, Service, ServiceName, Socket};
+
+mod client;
+mod limits;
+mod path;
+mod punch;
+mod reflect;
+pub use client::{Error, RelayClient, RelaySocket};
+pub use limits::{Limit, Limits};
+pub use path::{PathError, PathInfo, PathSocket};
+pub use punch::{HolePunch, Path, PunchError};
+pub use reflect::{Nat, Reflect};
+
+use limits::Quota;
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Msg<A> {
+    Reserve,
+    Reserved(u64),
+    Forward(A, Vec<u8>),
+    Broadcast(Vec<u8>),
+    Deliver(A, Vec<u8>),
+    Unreachable(A),
+    Denied(Option<A>, Limit),
+    BroadcastDenied(Limit),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RelayStats {
+    pub forwarded: u64,
+    pub unreachable: u64,
+    pub denied: u64,
+}
+
+struct State<A> {
+    reservations: BTreeMap<A, Instant>,
+    circuits: BTreeMap<(A, A), Instant>,
+    expired: BTreeMap<(A, A), Instant>,
+    quotas: BTreeMap<A, Quota>,
+    stats: RelayStats,
+}
+
+impl<A: Ord + Clone> State<A> {
+    fn expire(&mut self, limits: &Limits, now: Instant) {
+        self.reservations.retain(|_, expiry| *expiry > now);
+        let reservations = &self.reservations;
+        self.circuits
+            .retain(|(_, to), _| reservations.contains_key(to));
+        self.quotas.retain(|_, quota| !quota.idle(limits, now));
+        // An expired circuit stays closed for another circuit_duration so the
+        // next Forward cannot simply reopen it.
+        let live = |started: &Instant| now.duration_since(*started) < limits.circuit_duration;
+        let expired: Vec<_> = self
+            .circuits
+            .iter()
+            .filter(|(_, started)| !live(started))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            self.circuits.remove(&key);
+            if let Some(until) = now.checked_add(limits.circuit_duration) {
+                self.expired.insert(key, until);
+            }
+        }
+        self.expired.retain(|_, until| *until > now);
+    }
+
+    fn reserve(&mut self, addr: &A, limits: &Limits, now: Instant) -> Result<(), Limit> {
+        if !self.reservations.contains_key(addr)
+            && self.reservations.len() >= limits.max_reservations
+        {
+            return Err(Limit::Reservations);
+        }
+        self.reservations
+            .insert(addr.clone(), now + limits.reservation_ttl);
+        Ok(())
+    }
+
+    fn quota(
+        &mut self,
+        addr: &A,
+        bytes: usize,
+        limits: &Limits,
+        now: Instant,
+    ) -> Result<(), Limit> {
+        self.quotas
+            .entry(addr.clone())
+            .or_insert_with(|| Quota::new(limits, now))
+            .take(limits, bytes, now)
+    }
+
+    fn circuit(&mut self, from: &A, to: &A, limits: &Limits, now: Instant) -> Result<(), Limit> {
+        let key = (from.clone(), to.clone());
+        if self.expired.contains_key(&key) {
+            return Err(Limit::Duration);
+        }
+        if self.circuits.contains_key(&key) {
+            return Ok(());
+        }
+        let per_peer = self.circuits.keys().filter(|(a, _)| a == from).count();
+        if self.circuits.len() >= limits.max_circuits || per_peer >= limits.max_circuits_per_peer {
+            return Err(Limit::Circuits);
+        }
+        self.circuits.insert(key, now);
+        Ok(())
+    }
+}
+
+pub struct RelayService<A> {
+    limits: Limits,
+    state: Arc<Mutex<State<A>>>,
+}
+
+impl<A> Clone for RelayService<A> {
+    fn clone(&self) -> Self {
+        Self {
+            limits: self.limits.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<A: Ord + Clone> RelayService<A> {
+    pub fn new() -> Self {
+        Self {
+            limits: Limits::default(),
+            state: Arc::new(Mutex::new(State {
+                reservations: BTreeMap::new(),
+                circuits: BTreeMap::new(),
+                expired: BTreeMap::new(),
+                quotas: BTreeMap::new(),
+                stats: RelayStats::default(),
+            })),
+        }
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn peers(&self) -> Vec<A> {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        state
+            .reservations
+            .iter()
+            .filter(|(_, expiry)| **expiry > now)
+            .map(|(addr, _)| addr.clone())
+            .collect()
+    }
+
+    pub fn circuits(&self) -> Vec<(A, A)> {
+        let state = self.state.lock().unwrap();
+        state.circuits.keys().cloned().collect()
+    }
+
+    pub fn stats(&self) -> RelayStats {
+        self.state.lock().unwrap().stats
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut State<A>, &Limits, Instant) -> T) -> T {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.expire(&self.limits, now);
+        f(&mut state, &self.limits, now)
+    }
+
+    fn count(&self, outcome: Result<bool, Limit>) {
+        let mut state = self.state.lock().unwrap();
+        match outcome {
+            Ok(true) => state.stats.forwarded += 1,
+            Ok(false) => state.stats.unreachable += 1,
+            Err(_) => state.stats.denied += 1,
+        }
+    }
+}
+
+impl<A: Ord + Clone> Default for RelayService<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A> ServiceName for RelayService<A> {
+    type Name = &'static str;
+
+    fn name(&self) -> Self::Name {
+        "relay"
+    }
+}
+
+impl<S> Service<S> for RelayService<S::Addr>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug + Serialize + DeserializeOwned,
+    S::Error: Debug,
+{
+    type Output = ();
+
+    type Error = S::Error;
+
+    async fn upgrade(&self, mut socket: S) -> Result<Self::Output, Self::Error> {
+        loop {
+            let (Lenient(msg), from) = socket.recv::<Lenient<Msg<S::Addr>>>().await?;
+            let Some(msg) = msg else {
+                yanet_core::debug!(peer = ?from, "dropping unreadable relay message");
+                continue;
+            };
+            let reply = match msg {
+                Msg::Reserve => {
+                    let reserved = self.with(|state, limits, now| {
+                        state.quota(&from, 0, limits, now)?;
+                        state.reserve(&from, limits, now)
+                    });
+                    match reserved {
+                        Ok(()) => {
+                            yanet_core::debug!(peer = ?from, "reservation accepted");
+                            let ttl = self.limits.reservation_ttl.as_millis() as u64;
+                            Some(Msg::Reserved(ttl))
+                        }
+                        Err(limit) => {
+                            yanet_core::debug!(peer = ?from, ?limit, "reservation denied");
+                            self.count(Err(limit));
+                            Some(Msg::Denied(None, limit))
+                        }
+                    }
+                }
+                Msg::Forward(to, data) => {
+                    yanet_core::trace!(peer = ?from, to = ?to, len = data.len(), "forwarding");
+                    let admitted = self.with(|state, limits, now| {
+                        state.quota(&from, data.len(), limits, now)?;
+                        if !state.reservations.contains_key(&to) {
+                            return Ok(false);
+                        }
+                        state.circuit(&from, &to, limits, now)?;
+                        Ok(true)
+                    });
+                    let outcome = match admitted {
+                        Ok(true) => Ok(socket
+                            .send(&Msg::Deliver(from.clone(), data), to.clone())
+                            .await
+                            .is_ok()),
+                        outcome => outcome,
+                    };
+                    self.count(outcome);
+                    match outcome {
+                        Ok(true) => None,
+                        Ok(false) => {
+                            yanet_core::debug!(peer = ?from, to = ?to, "destination unreachable");
+                            Some(Msg::Unreachable(to))
+                        }
+                        Err(limit) => {
+                            yanet_core::debug!(peer = ?from, to = ?to, ?limit, "forward denied");
+                            Some(Msg::Denied(Some(to), limit))
+                        }
+                    }
+                }
+                Msg::Broadcast(data) => {
+                    yanet_core::trace!(peer = ?from, len = data.len(), "broadcasting");
+                    let admitted = self.with(|state, limits, now| {
+                        let peers: Vec<_> = state
+                            .reservations
+                            .keys()
+                            .filter(|to| **to != from)
+                            .cloned()
+                            .collect();
+                        state.quota(&from, data.len() * peers.len(), limits, now)?;
+                        let mut denied = None;
+                        let peers: Vec<_> = peers
+                            .into_iter()
+                            .filter(|to| match state.circuit(&from, to, limits, now) {
+                                Ok(()) => true,
+                                Err(limit) => {
+                                    denied = Some(limit);
+                                    false
+                                }
+                            })
+                            .collect();
+                        match denied {
+                            Some(limit) if peers.is_empty() => Err(limit),
+                            _ => Ok((peers, denied)),
+                        }
+                    });
+                    match admitted {
+                        Ok((peers, denied)) => {
+                            for to in peers {
+                                let deliver = Msg::Deliver(from.clone(), data.clone());
+                                let sent = socket.send(&deliver, to).await.is_ok();
+                                self.count(Ok(sent));
+                            }
+                            denied.map(|limit| {
+                                yanet_core::debug!(peer = ?from, ?limit, "broadcast partly denied");
+                                self.count(Err(limit));
+                                Msg::BroadcastDenied(limit)
+                            })
+                        }
+                        Err(limit) => {
+                            yanet_core::debug!(peer = ?from, ?limit, "broadcast denied");
+                            self.count(Err(limit));
+                            Some(Msg::BroadcastDenied(limit))
+                        }
+                    }
+                }
+                _ => {
+                    yanet_core::debug!(peer = ?from, "unexpected relay message");
+                    None
+                }
+            };
+            if let Some(reply) = reply {
+                if let Err(err) = socket.send(&reply, from).await {
+                    yanet_core::debug!(error = ?err, "relay reply failed");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Reservations,
+    Circuits,
+    Duration,
+    Rate,
+    Bandwidth,
+}
+
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub(crate) max_reservations: usize,
+    pub(crate) reservation_ttl: Duration,
+    pub(crate) max_circuits: usize,
+    pub(crate) max_circuits_per_peer: usize,
+    pub(crate) circuit_duration: Duration,
+    pub(crate) rate: f64,
+    pub(crate) rate_burst: f64,
+    pub(crate) bandwidth: f64,
+    pub(crate) bandwidth_burst: f64,
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Self {
+            max_reservations: usize::MAX,
+            reservation_ttl: Duration::from_secs(60),
+            max_circuits: usize::MAX,
+            max_circuits_per_peer: usize::MAX,
+            circuit_duration: Duration::MAX,
+            rate: f64::INFINITY,
+            rate_burst: f64::INFINITY,
+            bandwidth: f64::INFINITY,
+            bandwidth_burst: f64::INFINITY,
+        }
+    }
+
+    pub fn max_reservations(mut self, max: usize) -> Self {
+        self.max_reservations = max;
+        self
+    }
+
+    pub fn reservation_ttl(mut self, ttl: Duration) -> Self {
+        self.reservation_ttl = ttl;
+        self
+    }
+
+    pub fn max_circuits(mut self, max: usize) -> Self {
+        self.max_circuits = max;
+        self
+    }
+
+    pub fn max_circuits_per_peer(mut self, max: usize) -> Self {
+        self.max_circuits_per_peer = max;
+        self
+    }
+
+    pub fn circuit_duration(mut self, duration: Duration) -> Self {
+        self.circuit_duration = duration;
+        self
+    }
+
+    pub fn rate(mut self, per_sec: u32, burst: u32) -> Self {
+        self.rate = per_sec as f64;
+        self.rate_burst = burst.max(1) as f64;
+        self
+    }
+
+    pub fn bandwidth(mut self, bytes_per_sec: u32, burst: u32) -> Self {
+        self.bandwidth = bytes_per_sec as f64;
+        self.bandwidth_burst = burst.max(1) as f64;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::unlimited()
+            .max_reservations(128)
+            .max_circuits(64)
+            .max_circuits_per_peer(8)
+            .circuit_duration(Duration::from_secs(120))
+            .rate(100, 200)
+            .bandwidth(64 * 1024, 128 * 1024)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(burst: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, rate: f64, burst: f64, now: Instant) {
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.last = now;
+    }
+
+    fn take(&mut self, amount: f64, rate: f64, burst: f64, now: Instant) -> bool {
+        self.refill(rate, burst, now);
+        let ok = self.tokens >= amount;
+        if ok {
+            self.tokens -= amount;
+        }
+        ok
+    }
+
+    fn full(&self, rate: f64, burst: f64, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens >= burst || self.tokens + elapsed * rate >= burst
+    }
+}
+
+pub(crate) struct Quota {
+    messages: Bucket,
+    bytes: Bucket,
+}
+
+impl Quota {
+    pub(crate) fn new(limits: &Limits, now: Instant) -> Self {
+        Self {
+            messages: Bucket::new(limits.rate_burst, now),
+            bytes: Bucket::new(limits.bandwidth_burst, now),
+        }
+    }
+
+    pub(crate) fn take(
+        &mut self,
+        limits: &Limits,
+        bytes: usize,
+        now: Instant,
+    ) -> Result<(), Limit> {
+        self.messages.refill(limits.rate, limits.rate_burst, now);
+        if self.messages.tokens < 1.0 {
+            return Err(Limit::Rate);
+        }
+        let (rate, burst) = (limits.bandwidth, limits.bandwidth_burst);
+        if !self.bytes.take(bytes as f64, rate, burst, now) {
+            return Err(Limit::Bandwidth);
+        }
+        self.messages.take(1.0, limits.rate, limits.rate_burst, now);
+        Ok(())
+    }
+
+    pub(crate) fn idle(&self, limits: &Limits, now: Instant) -> bool {
+        self.messages.full(limits.rate, limits.rate_burst, now)
+            && self
+                .bytes
+                .full(limits.bandwidth, limits.bandwidth_burst, now)
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use yanet_core::{BroadcastError, Socket};
+
+#[derive(Serialize)]
+enum FrameRef<'a, D: ?Sized> {
+    Data(&'a D),
+    Probe(u64),
+    Ack(u64),
+}
+
+#[derive(Deserialize)]
+enum Frame<D> {
+    Data(D),
+    Probe(u64),
+    Ack(u64),
+}
+
+#[derive(Debug)]
+pub enum PathError<K, E> {
+    Socket(E),
+    NoPath(K),
+    Broadcast(BroadcastError<K, PathError<K, E>>),
+}
+
+#[derive(Clone, Debug)]
+pub struct PathInfo<A> {
+    pub addr: A,
+    pub up: bool,
+    pub rtt: Option<Duration>,
+}
+
+struct Route<A> {
+    addr: A,
+    up: bool,
+    srtt: Option<Duration>,
+    last_recv: Option<Instant>,
+    last_probe: Option<Instant>,
+    // Timestamp of the probe awaiting an Ack; any other Ack is stale or forged.
+    outstanding: Option<u64>,
+}
+
+impl<A> Route<A> {
+    fn record(&mut self, rtt: Duration) {
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        });
+    }
+}
+
+type Resolve<A, K> = Box<dyn Fn(&A) -> Option<K>>;
+
+pub struct PathSocket<S: Socket, K> {
+    inner: S,
+    interval: Duration,
+    misses: u32,
+    epoch: Instant,
+    peers: BTreeMap<K, Vec<Route<S::Addr>>>,
+    acks: VecDeque<(S::Addr, u64)>,
+    resolve: Option<Resolve<S::Addr, K>>,
+}
+
+impl<S, K> PathSocket<S, K>
+where
+    S: Socket,
+    S::Addr: Eq + Clone + Debug,
+    S::Error: Debug,
+    K: Ord + Clone + Debug,
+{
+    pub fn new(inner: S, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            misses: 3,
+            epoch: Instant::now(),
+            peers: BTreeMap::new(),
+            acks: VecDeque::new(),
+            resolve: None,
+        }
+    }
+
+    pub fn misses(mut self, misses: u32) -> Self {
+        self.misses = misses.max(1);
+        self
+    }
+
+    /// Frames from an address that no path was added for are dropped unless
+    /// `resolve` maps it to a peer, in which case the address becomes a new path.
+    pub fn learn(mut self, resolve: impl Fn(&S::Addr) -> Option<K> + 'static) -> Self {
+        self.resolve = Some(Box::new(resolve));
+        self
+    }
+
+    pub fn add_path(&mut self, peer: K, addr: S::Addr) {
+        let routes = self.peers.entry(peer).or_default();
+        if !routes.iter().any(|route| route.addr == addr) {
+            routes.push(Route {
+                addr,
+                up: false,
+                srtt: None,
+                last_recv: None,
+                last_probe: None,
+                outstanding: None,
+            });
+        }
+    }
+
+    pub fn remove_path(&mut self, peer: &K, addr: &S::Addr) -> bool {
+        let Some(routes) = self.peers.get_mut(peer) else {
+            return false;
+        };
+        let len = routes.len();
+        routes.retain(|route| route.addr != *addr);
+        let removed = routes.len() != len;
+        if routes.is_empty() {
+            self.peers.remove(peer);
+        }
+        removed
+    }
+
+    pub fn remove_peer(&mut self, peer: &K) -> bool {
+        self.peers.remove(peer).is_some()
+    }
+
+    pub fn paths(&self, peer: &K) -> Vec<PathInfo<S::Addr>> {
+        self.peers.get(peer).map_or(Vec::new(), |routes| {
+            routes
+                .iter()
+                .map(|route| PathInfo {
+                    addr: route.addr.clone(),
+                    up: route.up,
+                    rtt: route.srtt,
+                })
+                .collect()
+        })
+    }
+
+    pub fn best(&self, peer: &K) -> Option<S::Addr> {
+        let routes = self.peers.get(peer)?;
+        self.order(peer)
+            .first()
+            .map(|index| routes[*index].addr.clone())
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_micros() as u64
+    }
+
+    fn order(&self, peer: &K) -> Vec<usize> {
+        let Some(routes) = self.peers.get(peer) else {
+            return Vec::new();
+        };
+        let mut up: Vec<usize> = (0..routes.len()).filter(|i| routes[*i].up).collect();
+        if up.is_empty() {
+            return (0..routes.len()).collect();
+        }
+        up.sort_by_key(|i| (routes[*i].srtt.unwrap_or(Duration::MAX), *i));
+        up
+    }
+
+    fn route(&mut self, addr: &S::Addr) -> Option<(K, &mut Route<S::Addr>)> {
+        self.peers.iter_mut().find_map(|(peer, routes)| {
+            let route = routes.iter_mut().find(|route| route.addr == *addr)?;
+            Some((peer.clone(), route))
+        })
+    }
+
+    // Acks wait here rather than across an await in recv, so cancelling recv
+    // does not lose them.
+    async fn reply(&mut self) {
+        while let Some((addr, sent)) = self.acks.front().cloned() {
+            let ack = FrameRef::<()>::Ack(sent);
+            if let Err(err) = self.inner.send(&ack, addr.clone()).await {
+                yanet_core::trace!(path = ?addr, error = ?err, "path probe reply failed");
+            }
+            self.acks.pop_front();
+        }
+    }
+
+    async fn maintain(&mut self) -> Instant {
+        let now = Instant::now();
+        let dead_after = self.interval * self.misses;
+        let sent = self.now();
+        let mut due = Vec::new();
+        for (peer, routes) in self.peers.iter_mut() {
+            for route in routes.iter_mut() {
+                let alive = route
+                    .last_recv
+                    .is_some_and(|last| now.duration_since(last) < dead_after);
+                if route.up && !alive {
+                    yanet_core::debug!(peer = ?peer, path = ?route.addr, "path down");
+                    route.up = false;
+                }
+                if route
+                    .last_probe
+                    .is_none_or(|last| now.duration_since(last) >= self.interval)
+                {
+                    route.last_probe = Some(now);
+                    route.outstanding = Some(sent);
+                    due.push(route.addr.clone());
+                }
+            }
+        }
+        for addr in due {
+            let probe = FrameRef::<()>::Probe(sent);
+            if let Err(err) = self.inner.send(&probe, addr.clone()).await {
+                yanet_core::trace!(path = ?addr, error = ?err, "path probe failed");
+            }
+        }
+        self.peers
+            .values()
+            .flatten()
+            .filter_map(|route| route.last_probe)
+            .map(|last| last + self.interval)
+            .min()
+            .unwrap_or(now + self.interval)
+    }
+
+    fn received(&mut self, addr: &S::Addr) -> Option<K> {
+        if self.route(addr).is_none() {
+            let peer = self.resolve.as_ref().and_then(|resolve| resolve(addr))?;
+            yanet_core::debug!(peer = ?peer, path = ?addr, "learned path");
+            self.add_path(peer, addr.clone());
+        }
+        let (peer, route) = self.route(addr)?;
+        route.last_recv = Some(Instant::now());
+        if !route.up {
+            yanet_core::debug!(peer = ?peer, path = ?addr, "path up");
+            route.up = true;
+        }
+        Some(peer)
+    }
+}
+
+impl<S, K> Socket for PathSocket<S, K>
+where
+    S: Socket,
+    S::Addr: Eq + Clone + Debug,
+    S::Error: Debug,
+    K: Ord + Clone + Debug,
+{
+    type Addr = K;
+    type Error = PathError<K, S::Error>;
+
+    async fn broadcast<D>(&mut self, data: &D) -> Result<(), Self::Error>
+    where
+        D: Serialize,
+    {
+        let peers: Vec<K> = self.peers.keys().cloned().collect();
+        let mut failures = Vec::new();
+        for peer in peers {
+            if let Err(err) = self.send(data, peer.clone()).await {
+                yanet_core::debug!(peer = ?peer, error = ?err, "broadcast to peer failed");
+                failures.push((peer, err));
+            }
+        }
+        BroadcastError::check(failures).map_err(PathError::Broadcast)
+    }
+
+    async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
+    where
+        D: Serialize + ?Sized,
+    {
+        let mut last = PathError::NoPath(addr.clone());
+        for index in self.order(&addr) {
+            let path = self.peers[&addr][index].addr.clone();
+            yanet_core::trace!(peer = ?addr, path = ?path, "sending");
+            match self.inner.send(&FrameRef::Data(data), path.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    yanet_core::debug!(peer = ?addr, path = ?path, error = ?err, "path failed");
+                    if let Some(route) = self.peers.get_mut(&addr).map(|r| &mut r[index]) {
+                        route.up = false;
+                    }
+                    last = PathError::Socket(err);
+                }
+            }
+        }
+        Err(last)
+    }
+
+    async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
+    where
+        D: DeserializeOwned,
+    {
+        loop {
+            self.reply().await;
+            let wake = self.maintain().await;
+            let sleep = async {
+                futures_timer::Delay::new(wake.saturating_duration_since(Instant::now())).await;
+                None
+            };
+            let recv = async { Some(self.inner.recv::<Frame<D>>().await) };
+            let Some(recv) = futures_micro::or!(sleep, recv).await else {
+                continue;
+            };
+            let (frame, addr) = recv.map_err(PathError::Socket)?;
+            let peer = self.received(&addr);
+            match frame {
+                Frame::Data(data) => match peer {
+                    Some(peer) => return Ok((data, peer)),
+                    None => yanet_core::debug!(path = ?addr, "dropping data from unknown path"),
+                },
+                Frame::Probe(sent) => self.acks.push_back((addr, sent)),
+                Frame::Ack(sent) => {
+                    let rtt = Duration::from_micros(self.now().saturating_sub(sent));
+                    match self.route(&addr) {
+                        Some((peer, route)) if route.outstanding == Some(sent) => {
+                            yanet_core::trace!(peer = ?peer, path = ?addr, ?rtt, "path rtt");
+                            route.outstanding = None;
+                            route.record(rtt);
+                        }
+                        _ => yanet_core::trace!(path = ?addr, "ignoring unexpected ack"),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    cell::Cell,
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use yanet_core::Socket;
+
+use crate::Reflect;
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Signal<A> {
+    Connect(u64, Vec<A>),
+    Sync(u64, Vec<A>),
+    Done(u64, bool),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Punch {
+    Syn(u64),
+    Ack(u64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Path<A> {
+    Direct(A),
+    Relayed,
+}
+
+#[derive(Debug)]
+pub enum PunchError<E> {
+    Signal(E),
+    Nonce(getrandom::Error),
+    Timeout,
+}
+
+pub struct HolePunch<A> {
+    candidates: Vec<A>,
+    reflect: Option<(Reflect<A>, Vec<A>)>,
+    interval: Duration,
+    attempts: u32,
+    timeout: Duration,
+}
+
+impl<A> HolePunch<A>
+where
+    A: Ord + Clone + Debug + Serialize + DeserializeOwned,
+{
+    pub fn new(candidates: Vec<A>) -> Self {
+        Self {
+            candidates,
+            reflect: None,
+            interval: Duration::from_millis(100),
+            attempts: 10,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn reflect(mut self, reflect: Reflect<A>, servers: Vec<A>) -> Self {
+        self.reflect = Some((reflect, servers));
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn connect<R, U>(
+        &self,
+        signal: &mut R,
+        direct: &mut U,
+        peer: R::Addr,
+    ) -> Result<Path<A>, PunchError<R::Error>>
+    where
+        R: Socket,
+        R::Addr: Eq + Clone + Debug,
+        R::Error: Debug,
+        U: Socket<Addr = A>,
+        U::Error: Debug,
+    {
+        let mut nonce = [0u8; 8];
+        getrandom::getrandom(&mut nonce).map_err(PunchError::Nonce)?;
+        let nonce = u64::from_le_bytes(nonce);
+        yanet_core::debug!(peer = ?peer, nonce, "requesting hole punch");
+        signal
+            .send(
+                &Signal::Connect(nonce, self.candidates().await),
+                peer.clone(),
+            )
+            .await
+            .map_err(PunchError::Signal)?;
+        let deadline = Instant::now() + self.timeout;
+        let remote = loop {
+            let sleep = async {
+                futures_timer::Delay::new(deadline.saturating_duration_since(Instant::now())).await;
+                None
+            };
+            let recv = async { Some(signal.recv::<Signal<A>>().await) };
+            let Some(recv) = futures_micro::or!(sleep, recv).await else {
+                return Err(PunchError::Timeout);
+            };
+            match recv {
+                Ok((Signal::Sync(n, remote), from)) if n == nonce && from == peer => break remote,
+                Ok((msg, from)) => {
+                    yanet_core::debug!(peer = ?from, ?msg, "ignoring signal while connecting");
+                }
+                Err(err) => {
+                    yanet_core::debug!(error = ?err, "ignoring unreadable signal while connecting");
+                }
+            }
+        };
+        let seen = Cell::new(None);
+        let acked = self.punch(direct, nonce, &remote, &seen, true).await;
+        // The acceptor adopts this verdict, so both sides settle on the same path
+        // unless every copy is lost.
+        for _ in 0..self.attempts {
+            let done = Signal::<A>::Done(nonce, acked.is_some());
+            if let Err(err) = signal.send(&done, peer.clone()).await {
+                yanet_core::debug!(peer = ?peer, error = ?err, "hole punch verdict failed");
+            }
+        }
+        Ok(match acked {
+            Some(addr) => {
+                yanet_core::debug!(peer = ?addr, "hole punch succeeded");
+                Path::Direct(addr)
+            }
+            None => {
+                yanet_core::debug!(nonce, "hole punch failed, staying relayed");
+                Path::Relayed
+            }
+        })
+    }
+
+    pub async fn accept<R, U>(
+        &self,
+        signal: &mut R,
+        direct: &mut U,
+    ) -> Result<(R::Addr, Path<A>), PunchError<R::Error>>
+    where
+        R: Socket,
+        R::Addr: Eq + Clone + Debug,
+        R::Error: Debug,
+        U: Socket<Addr = A>,
+        U::Error: Debug,
+    {
+        let (nonce, remote, peer) = loop {
+            let (msg, from) = match signal.recv::<Signal<A>>().await {
+                Ok(received) => received,
+                Err(err) => {
+                    yanet_core::debug!(error = ?err, "ignoring unreadable signal while accepting");
+                    continue;
+                }
+            };
+            match msg {
+                Signal::Connect(nonce, remote) => break (nonce, remote, from),
+                msg => yanet_core::debug!(peer = ?from, ?msg, "ignoring signal while accepting"),
+            }
+        };
+        yanet_core::debug!(peer = ?peer, nonce, "accepting hole punch");
+        signal
+            .send(&Signal::Sync(nonce, self.candidates().await), peer.clone())
+            .await
+            .map_err(PunchError::Signal)?;
+        let seen = Cell::new(None);
+        let deadline = Instant::now() + self.timeout;
+        let punch = async {
+            self.punch(direct, nonce, &remote, &seen, false).await;
+            std::future::pending().await
+        };
+        let verdict = async {
+            loop {
+                match signal.recv::<Signal<A>>().await {
+                    Ok((Signal::Done(n, direct), from)) if n == nonce && from == peer => {
+                        return direct;
+                    }
+                    Ok((msg, from)) => {
+                        yanet_core::debug!(peer = ?from, ?msg, "ignoring signal while punching");
+                    }
+                    Err(err) => {
+                        yanet_core::debug!(error = ?err, "ignoring unreadable signal while punching");
+                    }
+                }
+            }
+        };
+        let sleep = async {
+            futures_timer::Delay::new(deadline.saturating_duration_since(Instant::now())).await;
+            yanet_core::debug!(peer = ?peer, "no hole punch verdict");
+            false
+        };
+        let direct = futures_micro::or!(verdict, punch, sleep).await;
+        // A direct verdict means the connector's Syn was acknowledged, so it was seen here.
+        let path = match seen.take() {
+            Some(addr) if direct => {
+                yanet_core::debug!(peer = ?addr, "hole punch succeeded");
+                Path::Direct(addr)
+            }
+            _ => {
+                yanet_core::debug!(nonce, "hole punch failed, staying relayed");
+                Path::Relayed
+            }
+        };
+        Ok((peer, path))
+    }
+
+    async fn candidates(&self) -> Vec<A> {
+        let mut candidates = self.candidates.clone();
+        let Some((reflect, servers)) = &self.reflect else {
+            return candidates;
+        };
+        for server in servers {
+            match reflect.observe(server.clone()).await {
+                Some(addr) if !candidates.contains(&addr) => candidates.push(addr),
+                Some(_) => {}
+                None => yanet_core::debug!(server = ?server, "no observed address"),
+            }
+        }
+        candidates
+    }
+
+    async fn punch<U>(
+        &self,
+        direct: &mut U,
+        nonce: u64,
+        remote: &[A],
+        seen: &Cell<Option<A>>,
+        until_acked: bool,
+    ) -> Option<A>
+    where
+        U: Socket<Addr = A>,
+        U::Error: Debug,
+    {
+        for _ in 0..self.attempts {
+            for addr in remote {
+                if let Err(err) = direct.send(&Punch::Syn(nonce), addr.clone()).await {
+                    yanet_core::trace!(peer = ?addr, error = ?err, "punch send failed");
+                }
+            }
+            let next = Instant::now() + self.interval;
+            loop {
+                let sleep = async {
+                    futures_timer::Delay::new(next.saturating_duration_since(Instant::now())).await;
+                    None
+                };
+                let recv = async { Some(direct.recv::<Punch>().await) };
+                let Some(recv) = futures_micro::or!(sleep, recv).await else {
+                    break;
+                };
+                match recv {
+                    Ok((Punch::Syn(n), addr)) if n == nonce => {
+                        yanet_core::trace!(peer = ?addr, "punch received");
+                        direct.send(&Punch::Ack(nonce), addr.clone()).await.ok();
+                        seen.set(Some(addr));
+                    }
+                    Ok((Punch::Ack(n), addr)) if n == nonce => {
+                        yanet_core::trace!(peer = ?addr, "punch acknowledged");
+                        if until_acked {
+                            return Some(addr);
+                        }
+                    }
+                    Ok((msg, addr)) => {
+                        yanet_core::trace!(peer = ?addr, ?msg, "ignoring stale punch");
+                    }
+                    Err(err) => {
+                        yanet_core::trace!(error = ?err, "ignoring unreadable datagram");
+                    }
+                }
+            }
+        }
+        None
+    }
+}
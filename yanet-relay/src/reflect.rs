@@ -0,0 +1,315 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_channel::Sender;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use yanet_core::{Service, ServiceName, Socket};
+use yanet_ping::{Commands, Wake};
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Msg<A> {
+    Request(u32, bool),
+    Redirect(u32, A),
+    Response(u32, A),
+    Partner(u32, Option<A>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nat {
+    Blocked,
+    FullCone,
+    Restricted,
+    Symmetric,
+    Unknown,
+}
+
+enum Answer<A> {
+    Mapped(A),
+    Unknown,
+    Timeout,
+}
+
+struct Command<A> {
+    server: A,
+    change: bool,
+    reply: Sender<Answer<A>>,
+}
+
+struct Pending<A> {
+    id: u32,
+    command: Command<A>,
+    responder: Option<A>,
+    early: Option<(A, A)>,
+    deadline: Instant,
+    tries: u32,
+}
+
+impl<A: Clone> Pending<A> {
+    fn new(id: u32, command: Command<A>) -> Self {
+        let responder = (!command.change).then(|| command.server.clone());
+        Self {
+            id,
+            command,
+            responder,
+            early: None,
+            deadline: Instant::now(),
+            tries: 0,
+        }
+    }
+
+    fn timed_out(&self) -> Answer<A> {
+        // Without a Partner reply there is no telling whether the partner's
+        // answer was filtered or never sent.
+        match self.responder {
+            None => Answer::Unknown,
+            Some(_) => Answer::Timeout,
+        }
+    }
+}
+
+pub struct Reflect<A> {
+    partner: Option<A>,
+    timeout: Duration,
+    attempts: u32,
+    observed: Arc<Mutex<BTreeMap<A, A>>>,
+    commands: Commands<Command<A>>,
+}
+
+impl<A: Clone> Clone for Reflect<A> {
+    fn clone(&self) -> Self {
+        Self {
+            partner: self.partner.clone(),
+            timeout: self.timeout,
+            attempts: self.attempts,
+            observed: self.observed.clone(),
+            commands: self.commands.clone(),
+        }
+    }
+}
+
+impl<A: Ord + Clone> Reflect<A> {
+    pub fn new() -> Self {
+        Self {
+            partner: None,
+            timeout: Duration::from_millis(500),
+            attempts: 3,
+            observed: Default::default(),
+            commands: Commands::new(16),
+        }
+    }
+
+    pub fn partner(mut self, partner: A) -> Self {
+        self.partner = Some(partner);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub async fn observe(&self, server: A) -> Option<A> {
+        match self.request(server, false).await {
+            Answer::Mapped(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    pub async fn classify(&self, servers: &[A]) -> Nat {
+        let mut mapped = Vec::new();
+        for server in servers {
+            if let Some(addr) = self.observe(server.clone()).await {
+                mapped.push((server.clone(), addr));
+            }
+        }
+        let Some((server, first)) = mapped.first().cloned() else {
+            return Nat::Blocked;
+        };
+        if mapped.iter().any(|(_, addr)| *addr != first) {
+            return Nat::Symmetric;
+        }
+        match self.request(server, true).await {
+            Answer::Mapped(_) => Nat::FullCone,
+            Answer::Unknown => Nat::Unknown,
+            Answer::Timeout => Nat::Restricted,
+        }
+    }
+
+    pub fn observed(&self) -> Vec<A> {
+        let observed = self.observed.lock().unwrap();
+        let mut addrs: Vec<A> = observed.values().cloned().collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    async fn request(&self, server: A, change: bool) -> Answer<A> {
+        let command = |reply| Command {
+            server,
+            change,
+            reply,
+        };
+        let limit = self.timeout * (self.attempts + 1);
+        let answer = self.commands.call(limit, command).await;
+        answer.unwrap_or(Answer::Timeout)
+    }
+}
+
+impl<A: Ord + Clone> Default for Reflect<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A> ServiceName for Reflect<A> {
+    type Name = &'static str;
+
+    fn name(&self) -> Self::Name {
+        "reflect"
+    }
+}
+
+impl<S> Service<S> for Reflect<S::Addr>
+where
+    S: Socket,
+    S::Addr: Ord + Clone + Debug + Serialize + DeserializeOwned,
+    S::Error: Debug,
+{
+    type Output = ();
+
+    type Error = S::Error;
+
+    async fn upgrade(&self, mut socket: S) -> Result<Self::Output, Self::Error> {
+        let mut id = 0u32;
+        let mut pending: Vec<Pending<S::Addr>> = Vec::new();
+        loop {
+            let now = Instant::now();
+            for request in pending.iter_mut().filter(|request| request.deadline <= now) {
+                request.tries += 1;
+                if request.tries > self.attempts {
+                    yanet_core::debug!(server = ?request.command.server, "reflection timed out");
+                    request.command.reply.try_send(request.timed_out()).ok();
+                    continue;
+                }
+                let msg = Msg::<S::Addr>::Request(request.id, request.command.change);
+                let server = request.command.server.clone();
+                if let Err(err) = socket.send(&msg, server).await {
+                    yanet_core::debug!(error = ?err, "reflection request failed");
+                }
+                request.deadline = Instant::now() + self.timeout;
+            }
+            pending.retain(|request| request.tries <= self.attempts);
+            let wake = pending
+                .iter()
+                .map(|request| request.deadline)
+                .min()
+                .unwrap_or(now + self.timeout);
+            match self
+                .commands
+                .next::<S, Msg<S::Addr>>(&mut socket, wake)
+                .await
+            {
+                Wake::Recv(Ok((Msg::Request(id, false), from))) => {
+                    yanet_core::trace!(peer = ?from, id, "reflecting address");
+                    let response = Msg::Response(id, from.clone());
+                    if let Err(err) = socket.send(&response, from).await {
+                        yanet_core::debug!(error = ?err, "reflection reply failed");
+                    }
+                }
+                Wake::Recv(Ok((Msg::Request(id, true), from))) => {
+                    let reply = Msg::Partner(id, self.partner.clone());
+                    if let Err(err) = socket.send(&reply, from.clone()).await {
+                        yanet_core::debug!(error = ?err, "reflection reply failed");
+                    }
+                    match self.partner.clone() {
+                        Some(partner) => {
+                            yanet_core::trace!(peer = ?from, id, "redirecting reflection to partner");
+                            socket.send(&Msg::Redirect(id, from), partner).await.ok();
+                        }
+                        None => {
+                            yanet_core::debug!(peer = ?from, "no partner for changed address request");
+                        }
+                    }
+                }
+                Wake::Recv(Ok((Msg::Redirect(id, target), from))) => {
+                    if self.partner.as_ref() == Some(&from) {
+                        yanet_core::trace!(peer = ?target, id, "reflecting address for partner");
+                        socket
+                            .send(&Msg::Response(id, target.clone()), target)
+                            .await
+                            .ok();
+                    } else {
+                        yanet_core::debug!(peer = ?from, "ignoring redirect from non-partner");
+                    }
+                }
+                Wake::Recv(Ok((Msg::Partner(id, partner), from))) => {
+                    let Some(index) = pending.iter().position(|request| {
+                        request.id == id && request.command.change && request.command.server == from
+                    }) else {
+                        yanet_core::trace!(peer = ?from, id, "ignoring stale partner");
+                        continue;
+                    };
+                    match partner {
+                        Some(partner) => {
+                            let request = &mut pending[index];
+                            request.responder = Some(partner.clone());
+                            // The partner's answer may overtake the server's reply.
+                            if let Some((_, mapped)) =
+                                request.early.take().filter(|(from, _)| *from == partner)
+                            {
+                                let request = pending.swap_remove(index);
+                                yanet_core::debug!(server = ?from, ?mapped, "changed address answered");
+                                request.command.reply.try_send(Answer::Mapped(mapped)).ok();
+                            }
+                        }
+                        None => {
+                            let request = pending.swap_remove(index);
+                            yanet_core::debug!(server = ?from, "server has no partner");
+                            request.command.reply.try_send(Answer::Unknown).ok();
+                        }
+                    }
+                }
+                Wake::Recv(Ok((Msg::Response(id, mapped), from))) => {
+                    let Some(index) = pending.iter().position(|request| {
+                        request.id == id && request.responder.as_ref() == Some(&from)
+                    }) else {
+                        let early = pending.iter_mut().find(|request| {
+                            request.id == id
+                                && request.command.change
+                                && request.responder.is_none()
+                        });
+                        match early {
+                            Some(request) => request.early = Some((from, mapped)),
+                            None => {
+                                yanet_core::trace!(peer = ?from, id, "ignoring stale reflection")
+                            }
+                        }
+                        continue;
+                    };
+                    let request = pending.swap_remove(index);
+                    yanet_core::debug!(server = ?request.command.server, ?mapped, "address observed");
+                    if !request.command.change {
+                        let mut observed = self.observed.lock().unwrap();
+                        observed.insert(request.command.server, mapped.clone());
+                    }
+                    request.command.reply.try_send(Answer::Mapped(mapped)).ok();
+                }
+                Wake::Recv(Err(err)) => return Err(err),
+                Wake::Command(command) => {
+                    id = id.wrapping_add(1);
+                    pending.push(Pending::new(id, command));
+                }
+                Wake::Timer => {}
+            }
+        }
+    }
+}
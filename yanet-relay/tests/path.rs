@@ -0,0 +1,128 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::Duration;
+
+use futures_lite::future::{block_on, or};
+use serde::Serialize;
+use yanet_core::Socket;
+use yanet_relay::PathSocket;
+use yanet_sim::{Network, SimSocket};
+
+type Paths = PathSocket<SimSocket, &'static str>;
+
+// Mirrors the frames PathSocket puts on the wire.
+#[derive(Serialize)]
+enum Frame {
+    #[allow(dead_code)]
+    Data(u32),
+    #[allow(dead_code)]
+    Probe(u64),
+    Ack(u64),
+}
+
+async fn pump<T>(socket: &mut Paths) -> T {
+    loop {
+        socket.recv::<u32>().await.unwrap();
+    }
+}
+
+async fn sleep(ms: u64) {
+    futures_timer::Delay::new(Duration::from_millis(ms)).await;
+}
+
+fn up(socket: &Paths, peer: &'static str) -> Vec<bool> {
+    socket.paths(&peer).iter().map(|path| path.up).collect()
+}
+
+#[test]
+fn fails_over_and_recovers() {
+    let net = Network::new(1);
+    let (a, b1, b2) = (net.node(), net.node(), net.node());
+    let (a_id, b1_id, b2_id) = (a.id(), b1.id(), b2.id());
+    let interval = Duration::from_millis(10);
+    let mut a = PathSocket::new(a, interval).misses(2);
+    a.add_path("b", b1_id);
+    a.add_path("b", b2_id);
+    let mut b1 = PathSocket::new(b1, interval);
+    b1.add_path("a", a_id);
+    let mut b2 = PathSocket::new(b2, interval);
+    b2.add_path("a", a_id);
+    block_on(async {
+        or(
+            or(pump(&mut a), pump(&mut b1)),
+            or(pump(&mut b2), sleep(100)),
+        )
+        .await;
+        assert_eq!(up(&a, "b"), vec![true, true]);
+
+        net.partition(&[a_id], &[b1_id]);
+        or(
+            or(pump(&mut a), pump(&mut b1)),
+            or(pump(&mut b2), sleep(100)),
+        )
+        .await;
+        assert_eq!(up(&a, "b"), vec![false, true]);
+        assert_eq!(a.best(&"b"), Some(b2_id));
+        a.send(&7u32, "b").await.unwrap();
+        let received = or(b2.recv::<u32>(), or(pump(&mut a), pump(&mut b1))).await;
+        assert_eq!(received.unwrap(), (7, "a"));
+
+        net.heal();
+        or(
+            or(pump(&mut a), pump(&mut b1)),
+            or(pump(&mut b2), sleep(100)),
+        )
+        .await;
+        assert_eq!(up(&a, "b"), vec![true, true]);
+    });
+}
+
+#[test]
+fn learns_paths_it_can_resolve() {
+    let net = Network::new(1);
+    let (a, b, mut stray) = (net.node(), net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let mut a = PathSocket::new(a, Duration::from_millis(10))
+        .learn(move |addr| (*addr == b_id).then_some("b"));
+    let mut b = PathSocket::new(b, Duration::from_millis(10));
+    b.add_path("a", a_id);
+    block_on(async {
+        stray.send(&Frame::Data(1), a_id).await.unwrap();
+        b.send(&2u32, "a").await.unwrap();
+        let received = or(a.recv::<u32>(), pump(&mut b)).await;
+        assert_eq!(received.unwrap(), (2, "b"));
+    });
+    let paths = a.paths(&"b");
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].addr, b_id);
+    assert!(paths[0].up);
+}
+
+#[test]
+fn only_the_outstanding_probe_is_acknowledged() {
+    let net = Network::new(1);
+    let (a, mut b) = (net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let mut a = PathSocket::new(a, Duration::from_secs(60));
+    a.add_path("b", b_id);
+    // The first recv probes b; forged Acks must not produce an rtt.
+    let sent = block_on(async {
+        let peer = async {
+            let (probe, _) = b.recv::<(u8, u64)>().await.unwrap();
+            b.send(&Frame::Ack(u64::MAX), a_id).await.unwrap();
+            b.send(&Frame::Ack(probe.1.wrapping_add(1)), a_id)
+                .await
+                .unwrap();
+            sleep(20).await;
+            probe.1
+        };
+        or(peer, pump(&mut a)).await
+    });
+    assert_eq!(a.paths(&"b")[0].rtt, None);
+    block_on(async {
+        b.send(&Frame::Ack(sent), a_id).await.unwrap();
+        or(sleep(20), pump(&mut a)).await;
+    });
+    assert!(a.paths(&"b")[0].rtt.is_some());
+}
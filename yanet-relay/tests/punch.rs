@@ -0,0 +1,80 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::Duration;
+
+use futures_lite::future::{block_on, or, zip};
+use yanet_core::Service;
+use yanet_relay::{HolePunch, Path, RelayClient, RelayService};
+use yanet_sim::{Link, Network, NodeId, SimSocket};
+
+async fn serve<T>(relay: RelayService<NodeId>, socket: SimSocket) -> T {
+    relay.upgrade(socket).await.unwrap();
+    unreachable!("relay service exited")
+}
+
+fn punch(candidate: NodeId) -> HolePunch<NodeId> {
+    HolePunch::new(vec![candidate])
+        .interval(Duration::from_millis(10))
+        .attempts(5)
+        .timeout(Duration::from_secs(1))
+}
+
+#[test]
+fn upgrades_to_a_direct_path_through_the_relay() {
+    let net = Network::new(1);
+    let relay = net.node();
+    let (a, b) = (net.node(), net.node());
+    let (mut a_direct, mut b_direct) = (net.node(), net.node());
+    let (relay_id, b_id) = (relay.id(), b.id());
+    let (a_direct_id, b_direct_id) = (a_direct.id(), b_direct.id());
+    let peers = async {
+        let mut a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        let mut b = RelayClient::new(relay_id).upgrade(b).await.unwrap();
+        let (connector, acceptor) = (punch(a_direct_id), punch(b_direct_id));
+        zip(
+            connector.connect(&mut a, &mut a_direct, b_id),
+            acceptor.accept(&mut b, &mut b_direct),
+        )
+        .await
+    };
+    let (connected, accepted) = block_on(or(peers, serve(RelayService::new(), relay)));
+    assert_eq!(connected.unwrap(), Path::Direct(b_direct_id));
+    let (_, path) = accepted.unwrap();
+    assert_eq!(path, Path::Direct(a_direct_id));
+}
+
+#[test]
+fn stays_relayed_when_direct_traffic_is_blocked() {
+    let net = Network::new(1);
+    let (mut a, mut b) = (net.node(), net.node());
+    let (mut a_direct, mut b_direct) = (net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let (a_direct_id, b_direct_id) = (a_direct.id(), b_direct.id());
+    net.partition(&[a_direct_id], &[b_direct_id]);
+    let (connector, acceptor) = (punch(a_direct_id), punch(b_direct_id));
+    let (connected, accepted) = block_on(zip(
+        connector.connect(&mut a, &mut a_direct, b_id),
+        acceptor.accept(&mut b, &mut b_direct),
+    ));
+    assert_eq!(connected.unwrap(), Path::Relayed);
+    assert_eq!(accepted.unwrap(), (a_id, Path::Relayed));
+}
+
+#[test]
+fn one_way_reachability_is_not_enough() {
+    let net = Network::new(1);
+    let (mut a, mut b) = (net.node(), net.node());
+    let (mut a_direct, mut b_direct) = (net.node(), net.node());
+    let b_id = b.id();
+    let (a_direct_id, b_direct_id) = (a_direct.id(), b_direct.id());
+    // Syns reach the acceptor, but nothing comes back.
+    net.set_link(b_direct_id, a_direct_id, Link::perfect().loss(1.0));
+    let (connector, acceptor) = (punch(a_direct_id), punch(b_direct_id));
+    let (connected, accepted) = block_on(zip(
+        connector.connect(&mut a, &mut a_direct, b_id),
+        acceptor.accept(&mut b, &mut b_direct),
+    ));
+    assert_eq!(connected.unwrap(), Path::Relayed);
+    assert_eq!(accepted.unwrap().1, Path::Relayed);
+}
@@ -0,0 +1,160 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::Duration;
+
+use futures_lite::future::{block_on, or};
+use serde::{de::DeserializeOwned, Serialize};
+use yanet_core::{Service, Socket};
+use yanet_muxer::Muxer;
+use yanet_relay::{Nat, Reflect};
+use yanet_sim::{Error, Link, Network, NodeId, SimSocket};
+
+// Maps the client to a different node per destination, like a symmetric NAT.
+struct Symmetric {
+    first: SimSocket,
+    second: SimSocket,
+    via_second: NodeId,
+}
+
+impl Socket for Symmetric {
+    type Addr = NodeId;
+    type Error = Error;
+
+    async fn broadcast<D: Serialize>(&mut self, data: &D) -> Result<(), Error> {
+        self.first.broadcast(data).await
+    }
+
+    async fn send<D: Serialize + ?Sized>(&mut self, data: &D, addr: NodeId) -> Result<(), Error> {
+        if addr == self.via_second {
+            self.second.send(data, addr).await
+        } else {
+            self.first.send(data, addr).await
+        }
+    }
+
+    async fn recv<D: DeserializeOwned>(&mut self) -> Result<(D, NodeId), Error> {
+        or(self.first.recv(), self.second.recv()).await
+    }
+}
+
+async fn serve<S>(reflect: Reflect<NodeId>, socket: S) -> Nat
+where
+    S: Socket<Addr = NodeId>,
+    S::Error: std::fmt::Debug,
+{
+    reflect.upgrade(socket).await.unwrap();
+    unreachable!("reflect service exited")
+}
+
+fn client() -> Reflect<NodeId> {
+    Reflect::new()
+        .timeout(Duration::from_millis(20))
+        .attempts(2)
+}
+
+fn classify<S>(net: &Network, socket: S, partners: bool) -> Nat
+where
+    S: Socket<Addr = NodeId>,
+    S::Error: std::fmt::Debug,
+{
+    let (s1, s2) = (net.node(), net.node());
+    let (id1, id2) = (s1.id(), s2.id());
+    let (mut r1, mut r2) = (Reflect::new(), Reflect::new());
+    if partners {
+        r1 = r1.partner(id2);
+        r2 = r2.partner(id1);
+    }
+    let client = client();
+    let servers = or(serve(r1, s1), serve(r2, s2));
+    let classify = async { client.classify(&[id1, id2]).await };
+    block_on(or(classify, or(serve(client.clone(), socket), servers)))
+}
+
+#[test]
+fn full_cone() {
+    let net = Network::new(1);
+    let socket = net.node();
+    assert_eq!(classify(&net, socket, true), Nat::FullCone);
+}
+
+#[test]
+fn restricted() {
+    let net = Network::new(1);
+    let socket = net.node();
+    let id = socket.id();
+    // Servers are created next as nodes 1 and 2; drop unsolicited traffic from the partner.
+    net.set_link(NodeId(2), id, Link::perfect().loss(1.0));
+    assert_eq!(classify(&net, socket, true), Nat::Restricted);
+}
+
+#[test]
+fn blocked() {
+    let net = Network::new(1);
+    let socket = net.node();
+    net.partition(&[socket.id()], &[NodeId(1), NodeId(2)]);
+    assert_eq!(classify(&net, socket, true), Nat::Blocked);
+}
+
+#[test]
+fn symmetric() {
+    let net = Network::new(1);
+    let (first, second) = (net.node(), net.node());
+    let socket = Symmetric {
+        first,
+        second,
+        via_second: NodeId(3),
+    };
+    assert_eq!(classify(&net, socket, true), Nat::Symmetric);
+}
+
+#[test]
+fn unknown_without_partner() {
+    let net = Network::new(1);
+    let socket = net.node();
+    assert_eq!(classify(&net, socket, false), Nat::Unknown);
+}
+
+#[test]
+fn ignores_spoofed_response() {
+    let net = Network::new(1);
+    let (socket, server, spoofer) = (net.node(), net.node(), net.node());
+    let (client_id, server_id) = (socket.id(), server.id());
+    net.set_link(server_id, client_id, Link::perfect().loss(1.0));
+    let client = client();
+    let spoof = async {
+        let mut spoofer = spoofer;
+        loop {
+            // Shaped like Msg::Response(id, addr), guessing the sequential ids.
+            for id in 0..8u32 {
+                let response = (2u32, id, NodeId(42));
+                spoofer.send(&response, client_id).await.unwrap();
+            }
+            futures_timer::Delay::new(Duration::from_millis(5)).await;
+        }
+    };
+    let observe = async { client.observe(server_id).await };
+    let services = or(serve(client.clone(), socket), serve(Reflect::new(), server));
+    let services = async {
+        services.await;
+        None
+    };
+    let observed = block_on(or(or(observe, spoof), services));
+    assert_eq!(observed, None);
+}
+
+#[test]
+fn returns_when_socket_closes() {
+    let net = Network::new(1);
+    let muxer = Muxer::new(net.node());
+    let first = muxer.socket("reflect");
+    // Registering the name again closes the first socket's channel.
+    let _second = muxer.socket("reflect");
+    assert!(block_on(Reflect::new().upgrade(first)).is_err());
+}
+
+#[test]
+fn gives_up_without_a_service() {
+    let reflect = client();
+    assert_eq!(block_on(reflect.observe(NodeId(1))), None);
+}
@@ -0,0 +1,220 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{sync::mpsc, thread, time::Duration};
+
+use futures_lite::future::{block_on, or};
+use yanet_core::{Service, Socket};
+use yanet_muxer::Muxer;
+use yanet_relay::{Error, Limit, Limits, RelayClient, RelayService, RelaySocket};
+use yanet_sim::{Network, NodeId, SimSocket};
+
+// Runs the test on its own thread so a loop that never yields fails instead of hanging.
+fn watchdog<T: Send + 'static>(test: impl FnOnce() -> T + Send + 'static) -> T {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || tx.send(test()).ok());
+    rx.recv_timeout(Duration::from_secs(5))
+        .expect("test did not finish")
+}
+
+async fn serve<T>(relay: RelayService<NodeId>, socket: SimSocket) -> T {
+    relay.upgrade(socket).await.unwrap();
+    unreachable!("relay service exited")
+}
+
+#[test]
+fn forwards_past_unreadable_datagrams() {
+    let net = Network::new(1);
+    let (relay, a, b, mut stray) = (net.node(), net.node(), net.node(), net.node());
+    let (relay_id, a_id, b_id) = (relay.id(), a.id(), b.id());
+    let clients = async {
+        stray.send(&u32::MAX, relay_id).await.unwrap();
+        let mut a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        let mut b = RelayClient::new(relay_id).upgrade(b).await.unwrap();
+        a.send(&7u32, b_id).await.unwrap();
+        b.recv::<u32>().await.unwrap()
+    };
+    let received = block_on(or(clients, serve(RelayService::new(), relay)));
+    assert_eq!(received, (7, a_id));
+}
+
+#[test]
+fn returns_when_socket_closes() {
+    let closed = watchdog(|| {
+        let net = Network::new(1);
+        let muxer = Muxer::new(net.node());
+        let first = muxer.socket("relay");
+        // Registering the name again closes the first socket's channel.
+        let _second = muxer.socket("relay");
+        block_on(RelayService::new().upgrade(first)).is_err()
+    });
+    assert!(closed);
+}
+
+#[test]
+fn short_reservations_do_not_spin() {
+    let sent = watchdog(|| {
+        let net = Network::new(1);
+        let (relay, client) = (net.node(), net.node());
+        let relay_id = relay.id();
+        let limits = Limits::unlimited().reservation_ttl(Duration::from_millis(1));
+        let idle = async {
+            let mut socket = RelayClient::new(relay_id).upgrade(client).await.unwrap();
+            let recv = async {
+                socket.recv::<u32>().await.unwrap();
+            };
+            let stop = futures_timer::Delay::new(Duration::from_millis(300));
+            or(stop, recv).await;
+        };
+        block_on(or(idle, serve(RelayService::new().limits(limits), relay)));
+        net.stats().sent
+    });
+    // A refresh every 100ms at most, each answered by the relay.
+    assert!(sent <= 12, "{sent}");
+}
+
+#[test]
+fn refused_refresh_is_an_error() {
+    let denied = watchdog(|| {
+        let net = Network::new(1);
+        let (relay, client) = (net.node(), net.node());
+        let relay_id = relay.id();
+        let limits = Limits::unlimited().rate(1, 1);
+        let refresh = async {
+            let mut socket = RelayClient::new(relay_id)
+                .refresh(Duration::from_millis(100))
+                .upgrade(client)
+                .await
+                .unwrap();
+            socket.recv::<u32>().await.unwrap_err()
+        };
+        let err = block_on(or(
+            refresh,
+            serve(RelayService::new().limits(limits), relay),
+        ));
+        matches!(err, Error::Denied(Limit::Rate))
+    });
+    assert!(denied);
+}
+
+// Lets the client process relay replies that do not surface as datagrams.
+async fn settle(socket: &mut RelaySocket<SimSocket>) {
+    let recv = async {
+        let received = socket.recv::<u32>().await;
+        panic!("unexpected datagram {received:?}");
+    };
+    or(futures_timer::Delay::new(Duration::from_millis(50)), recv).await;
+}
+
+#[test]
+fn unreserved_peers_are_unreachable() {
+    let net = Network::new(1);
+    let (relay, a, b) = (net.node(), net.node(), net.node());
+    let (relay_id, b_id) = (relay.id(), b.id());
+    let service = RelayService::new();
+    let client = async {
+        let mut a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        a.send(&1u32, b_id).await.unwrap();
+        settle(&mut a).await;
+        a.is_unreachable(&b_id)
+    };
+    assert!(block_on(or(client, serve(service.clone(), relay))));
+    assert_eq!(service.stats().unreachable, 1);
+    assert!(service.circuits().is_empty());
+}
+
+#[test]
+fn reservations_are_limited() {
+    let net = Network::new(1);
+    let (relay, a, b) = (net.node(), net.node(), net.node());
+    let (relay_id, a_id) = (relay.id(), a.id());
+    let service = RelayService::new().limits(Limits::unlimited().max_reservations(1));
+    let clients = async {
+        let _a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        RelayClient::new(relay_id).upgrade(b).await.err().unwrap()
+    };
+    let err = block_on(or(clients, serve(service.clone(), relay)));
+    assert!(matches!(err, Error::Denied(Limit::Reservations)));
+    assert_eq!(service.peers(), vec![a_id]);
+}
+
+#[test]
+fn circuits_per_peer_are_limited() {
+    let net = Network::new(1);
+    let (relay, a, b, c) = (net.node(), net.node(), net.node(), net.node());
+    let (relay_id, a_id, b_id, c_id) = (relay.id(), a.id(), b.id(), c.id());
+    let service = RelayService::new().limits(Limits::unlimited().max_circuits_per_peer(1));
+    let clients = async {
+        let mut a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        let mut b = RelayClient::new(relay_id).upgrade(b).await.unwrap();
+        let _c = RelayClient::new(relay_id).upgrade(c).await.unwrap();
+        a.send(&1u32, b_id).await.unwrap();
+        assert_eq!(b.recv::<u32>().await.unwrap(), (1, a_id));
+        a.send(&2u32, c_id).await.unwrap();
+        settle(&mut a).await;
+        a.denied()
+    };
+    assert_eq!(block_on(or(clients, serve(service.clone(), relay))), 1);
+    assert_eq!(service.circuits(), vec![(a_id, b_id)]);
+    assert_eq!(service.stats().denied, 1);
+}
+
+#[test]
+fn circuits_close_after_their_duration() {
+    let net = Network::new(1);
+    let (relay, a, b) = (net.node(), net.node(), net.node());
+    let (relay_id, a_id, b_id) = (relay.id(), a.id(), b.id());
+    let limits = Limits::unlimited().circuit_duration(Duration::from_millis(50));
+    let service = RelayService::new().limits(limits);
+    let clients = async {
+        let mut a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        let mut b = RelayClient::new(relay_id).upgrade(b).await.unwrap();
+        a.send(&1u32, b_id).await.unwrap();
+        assert_eq!(b.recv::<u32>().await.unwrap(), (1, a_id));
+        futures_timer::Delay::new(Duration::from_millis(60)).await;
+        a.send(&2u32, b_id).await.unwrap();
+        settle(&mut a).await;
+        a.denied()
+    };
+    assert_eq!(block_on(or(clients, serve(service.clone(), relay))), 1);
+    assert_eq!(service.stats().forwarded, 1);
+}
+
+#[test]
+fn message_rate_is_limited() {
+    let net = Network::new(1);
+    let (relay, a, b) = (net.node(), net.node(), net.node());
+    let (relay_id, b_id) = (relay.id(), b.id());
+    // The reservation takes one token from the burst of three.
+    let limits = Limits::unlimited().rate(1, 3);
+    let service = RelayService::new().limits(limits);
+    let clients = async {
+        let mut a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        let _b = RelayClient::new(relay_id).upgrade(b).await.unwrap();
+        for n in 0..5u32 {
+            a.send(&n, b_id).await.unwrap();
+        }
+        settle(&mut a).await;
+        a.denied()
+    };
+    assert_eq!(block_on(or(clients, serve(service.clone(), relay))), 3);
+    assert_eq!(service.stats().forwarded, 2);
+}
+
+#[test]
+fn bandwidth_is_limited() {
+    let net = Network::new(1);
+    let (relay, a, b) = (net.node(), net.node(), net.node());
+    let (relay_id, a_id, b_id) = (relay.id(), a.id(), b.id());
+    let service = RelayService::new().limits(Limits::unlimited().bandwidth(64, 64));
+    let clients = async {
+        let mut a = RelayClient::new(relay_id).upgrade(a).await.unwrap();
+        let mut b = RelayClient::new(relay_id).upgrade(b).await.unwrap();
+        a.send(&[0u8; 128][..], b_id).await.unwrap();
+        a.send(&1u32, b_id).await.unwrap();
+        assert_eq!(b.recv::<u32>().await.unwrap(), (1, a_id));
+        settle(&mut a).await;
+        a.denied()
+    };
+    assert_eq!(block_on(or(clients, serve(service.clone(), relay))), 1);
+}
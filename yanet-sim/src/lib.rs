@@ -0,0 +1,188 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+    fmt,
+    rc::Rc,
+    task::Waker,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use yanet_core::BroadcastError;
+
+mod link;
+mod socket;
+pub use link::Link;
+pub use socket::SimSocket;
+
+use link::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct NodeId(pub u64);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node{}", self.0)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    TooLarge(usize),
+    Serde(postcard::Error),
+    Broadcast(BroadcastError<NodeId, Error>),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub sent: u64,
+    pub delivered: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Packet {
+    at: Duration,
+    seq: u64,
+    from: NodeId,
+    dat: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Node {
+    queue: BinaryHeap<Reverse<Packet>>,
+    waker: Option<Waker>,
+}
+
+struct State {
+    now: Duration,
+    rng: Rng,
+    next_id: u64,
+    seq: u64,
+    nodes: BTreeMap<NodeId, Node>,
+    default: Link,
+    links: BTreeMap<(NodeId, NodeId), Link>,
+    blocked: BTreeSet<(NodeId, NodeId)>,
+    stats: Stats,
+}
+
+impl State {
+    fn transmit(&mut self, from: NodeId, to: NodeId, dat: &[u8]) -> Result<(), Error> {
+        let link = self.links.get(&(from, to)).unwrap_or(&self.default).clone();
+        if matches!(link.mtu, Some(mtu) if dat.len() > mtu) {
+            return Err(Error::TooLarge(dat.len()));
+        }
+        self.stats.sent += 1;
+        if self.blocked.contains(&(from, to))
+            || !self.nodes.contains_key(&to)
+            || self.rng.chance(link.loss)
+        {
+            yanet_core::trace!(%from, %to, len = dat.len(), "dropping packet");
+            self.stats.dropped += 1;
+            return Ok(());
+        }
+        let copies = if self.rng.chance(link.duplicate) {
+            self.stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let at = self.now + link.delay(&mut self.rng);
+            self.seq += 1;
+            let node = self.nodes.get_mut(&to).expect("checked above");
+            node.queue.push(Reverse(Packet {
+                at,
+                seq: self.seq,
+                from,
+                dat: dat.to_vec(),
+            }));
+            if let Some(waker) = node.waker.take() {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Network {
+    state: Rc<RefCell<State>>,
+}
+
+impl Network {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State {
+                now: Duration::ZERO,
+                rng: Rng::new(seed),
+                next_id: 0,
+                seq: 0,
+                nodes: BTreeMap::new(),
+                default: Link::default(),
+                links: BTreeMap::new(),
+                blocked: BTreeSet::new(),
+                stats: Stats::default(),
+            })),
+        }
+    }
+
+    pub fn node(&self) -> SimSocket {
+        let mut state = self.state.borrow_mut();
+        let id = NodeId(state.next_id);
+        state.next_id += 1;
+        state.nodes.insert(id, Node::default());
+        SimSocket::new(id, self.clone())
+    }
+
+    pub fn nodes(&self) -> Vec<NodeId> {
+        self.state.borrow().nodes.keys().copied().collect()
+    }
+
+    pub fn set_default_link(&self, link: Link) {
+        self.state.borrow_mut().default = link;
+    }
+
+    pub fn set_link(&self, from: NodeId, to: NodeId, link: Link) {
+        self.state.borrow_mut().links.insert((from, to), link);
+    }
+
+    pub fn partition(&self, a: &[NodeId], b: &[NodeId]) {
+        let mut state = self.state.borrow_mut();
+        for &x in a {
+            for &y in b {
+                state.blocked.insert((x, y));
+                state.blocked.insert((y, x));
+            }
+        }
+    }
+
+    pub fn heal(&self) {
+        self.state.borrow_mut().blocked.clear();
+    }
+
+    pub fn now(&self) -> Duration {
+        self.state.borrow().now
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.borrow_mut();
+        state.now += by;
+        let now = state.now;
+        for node in state.nodes.values_mut() {
+            let due = matches!(node.queue.peek(), Some(Reverse(packet)) if packet.at <= now);
+            if let Some(waker) = due.then(|| node.waker.take()).flatten() {
+                waker.wake();
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.state.borrow().stats
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    cmp::Reverse,
+    task::{Context, Poll},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use yanet_core::{BroadcastError, Socket};
+
+use crate::{Error, Network, NodeId};
+
+pub struct SimSocket {
+    id: NodeId,
+    net: Network,
+}
+
+impl SimSocket {
+    pub(crate) fn new(id: NodeId, net: Network) -> Self {
+        Self { id, net }
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.net
+    }
+
+    fn poll_recv_raw(&mut self, cx: &mut Context<'_>) -> Poll<(Vec<u8>, NodeId)> {
+        let mut state = self.net.state.borrow_mut();
+        let now = state.now;
+        let node = state
+            .nodes
+            .get_mut(&self.id)
+            .expect("node is registered while its socket is alive");
+        node.waker = Some(cx.waker().clone());
+        match node.queue.peek() {
+            Some(Reverse(packet)) if packet.at <= now => {
+                let Reverse(packet) = node.queue.pop().expect("peeked above");
+                state.stats.delivered += 1;
+                Poll::Ready((packet.dat, packet.from))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for SimSocket {
+    fn drop(&mut self) {
+        self.net.state.borrow_mut().nodes.remove(&self.id);
+    }
+}
+
+impl Socket for SimSocket {
+    type Addr = NodeId;
+    type Error = Error;
+
+    async fn broadcast<D>(&mut self, data: &D) -> Result<(), Self::Error>
+    where
+        D: Serialize,
+    {
+        let dat = postcard::to_allocvec(data).map_err(Error::Serde)?;
+        let mut state = self.net.state.borrow_mut();
+        let targets: Vec<NodeId> = state
+            .nodes
+            .keys()
+            .copied()
+            .filter(|id| *id != self.id)
+            .collect();
+        let mut failures = Vec::new();
+        for to in targets {
+            if let Err(err) = state.transmit(self.id, to, &dat) {
+                failures.push((to, err));
+            }
+        }
+        BroadcastError::check(failures).map_err(Error::Broadcast)
+    }
+
+    async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
+    where
+        D: Serialize + ?Sized,
+    {
+        let dat = postcard::to_allocvec(data).map_err(Error::Serde)?;
+        self.net.state.borrow_mut().transmit(self.id, addr, &dat)
+    }
+
+    async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
+    where
+        D: DeserializeOwned,
+    {
+        let (dat, from) = futures_lite::future::poll_fn(|cx| self.poll_recv_raw(cx)).await;
+        Ok((postcard::from_bytes(&dat).map_err(Error::Serde)?, from))
+    }
+}
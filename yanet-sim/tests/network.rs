@@ -0,0 +1,112 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::Duration;
+
+use futures_lite::future::{block_on, poll_once};
+use yanet_core::Socket;
+use yanet_sim::{Link, Network, SimSocket};
+
+fn drain(socket: &mut SimSocket) -> Vec<u32> {
+    let mut got = Vec::new();
+    while let Some(Ok((n, _))) = block_on(poll_once(socket.recv::<u32>())) {
+        got.push(n);
+    }
+    got
+}
+
+#[test]
+fn delivers_after_latency() {
+    let net = Network::new(1);
+    net.set_default_link(Link::perfect().latency(Duration::from_millis(10)));
+    let mut a = net.node();
+    let mut b = net.node();
+    block_on(a.send(&7u32, b.id())).unwrap();
+    net.advance(Duration::from_millis(9));
+    assert!(drain(&mut b).is_empty());
+    net.advance(Duration::from_millis(1));
+    assert_eq!(drain(&mut b), vec![7]);
+    assert_eq!(net.now(), Duration::from_millis(10));
+}
+
+#[test]
+fn loss_is_deterministic() {
+    let run = |seed| {
+        let net = Network::new(seed);
+        net.set_default_link(Link::perfect().loss(0.3));
+        let mut a = net.node();
+        let mut b = net.node();
+        for n in 0..1000u32 {
+            block_on(a.send(&n, b.id())).unwrap();
+        }
+        let got = drain(&mut b);
+        let stats = net.stats();
+        assert_eq!(stats.sent, 1000);
+        assert_eq!(stats.dropped + got.len() as u64, 1000);
+        assert!((200..400).contains(&stats.dropped), "{stats:?}");
+        got
+    };
+    assert_eq!(run(3), run(3));
+    assert_ne!(run(3), run(4));
+}
+
+#[test]
+fn duplicates_packets() {
+    let net = Network::new(1);
+    net.set_default_link(Link::perfect().duplicate(1.0));
+    let mut a = net.node();
+    let mut b = net.node();
+    for n in 0..10u32 {
+        block_on(a.send(&n, b.id())).unwrap();
+    }
+    let got = drain(&mut b);
+    assert_eq!(got.len(), 20);
+    assert_eq!(net.stats().duplicated, 10);
+    for n in 0..10u32 {
+        assert_eq!(got.iter().filter(|m| **m == n).count(), 2);
+    }
+}
+
+#[test]
+fn partition_and_heal() {
+    let net = Network::new(1);
+    let mut a = net.node();
+    let mut b = net.node();
+    let mut c = net.node();
+    net.partition(&[a.id()], &[b.id()]);
+    block_on(a.broadcast(&1u32)).unwrap();
+    assert!(drain(&mut b).is_empty());
+    assert_eq!(drain(&mut c), vec![1]);
+    net.heal();
+    block_on(a.broadcast(&2u32)).unwrap();
+    assert_eq!(drain(&mut b), vec![2]);
+    assert_eq!(drain(&mut c), vec![2]);
+    assert_eq!(net.stats().dropped, 1);
+}
+
+#[test]
+fn rejects_oversized_packets() {
+    let net = Network::new(1);
+    let mut a = net.node();
+    let b = net.node();
+    net.set_link(a.id(), b.id(), Link::perfect().mtu(8));
+    assert!(block_on(a.send(&[0u8; 16], b.id())).is_err());
+    assert!(block_on(a.send(&[0u8; 4], b.id())).is_ok());
+}
+
+#[test]
+fn broadcast_reports_each_failed_node() {
+    let net = Network::new(1);
+    let mut a = net.node();
+    let mut small = net.node();
+    let mut b = net.node();
+    net.set_link(a.id(), small.id(), Link::perfect().mtu(4));
+    let err = block_on(a.broadcast(&u32::MAX)).unwrap_err();
+    let yanet_sim::Error::Broadcast(err) = err else {
+        panic!("expected a broadcast error, got {err:?}");
+    };
+    assert_eq!(err.failures.len(), 1);
+    assert_eq!(err.failures[0].0, small.id());
+    assert!(drain(&mut small).is_empty());
+    assert_eq!(drain(&mut b), vec![u32::MAX]);
+}
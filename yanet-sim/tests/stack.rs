@@ -0,0 +1,85 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::Duration;
+
+use futures_lite::future::{block_on, or};
+use yanet_core::Socket;
+use yanet_muxer::Muxer;
+use yanet_noise::NoiseSocket;
+use yanet_ping::{Event, Pinger};
+use yanet_sim::{Network, SimSocket};
+
+async fn node(socket: SimSocket, key: u8, pinger: Pinger<[u8; 32]>, advertise: bool) {
+    let mut noise = NoiseSocket::new([key; 32], socket);
+    if advertise {
+        noise.advertise().await.unwrap();
+    }
+    Muxer::new(noise).handle(pinger).await.unwrap();
+}
+
+#[test]
+fn noise_muxer_pinger_round_trip() {
+    let net = Network::new(1);
+    let a = net.node();
+    let b = net.node();
+    let pa = Pinger::new(Duration::from_millis(10));
+    let pb = Pinger::new(Duration::from_millis(10));
+    let events = pa.events();
+    let nodes = or(node(a, 1, pa.clone(), true), node(b, 2, pb.clone(), false));
+    let rtt = async {
+        loop {
+            if let Event::Rtt(peer, _) = events.recv().await.unwrap() {
+                return peer;
+            }
+        }
+    };
+    let timeout = async {
+        futures_timer::Delay::new(Duration::from_secs(5)).await;
+        panic!("no pong within timeout");
+    };
+    let peer = block_on(or(
+        or(rtt, async {
+            nodes.await;
+            unreachable!()
+        }),
+        timeout,
+    ));
+    assert_eq!(pa.alive(), vec![peer]);
+    assert!(pa.peer(&peer).unwrap().received > 0);
+    assert_eq!(pb.alive().len(), 1);
+}
+
+#[test]
+fn stack_survives_stray_datagrams() {
+    let net = Network::new(1);
+    let (a, b, mut stray) = (net.node(), net.node(), net.node());
+    let (a_id, b_id) = (a.id(), b.id());
+    let pa = Pinger::new(Duration::from_millis(10));
+    let events = pa.events();
+    let nodes = or(
+        node(a, 1, pa.clone(), true),
+        node(b, 2, Pinger::new(Duration::from_millis(10)), false),
+    );
+    let rtt = async {
+        for to in [a_id, b_id] {
+            // Neither a Noise message nor a valid handshake.
+            stray.send(&u32::MAX, to).await.unwrap();
+            stray.send(&(1u8, vec![0u8; 8]), to).await.unwrap();
+        }
+        loop {
+            if let Event::Rtt(peer, _) = events.recv().await.unwrap() {
+                return peer;
+            }
+        }
+    };
+    let timeout = async {
+        futures_timer::Delay::new(Duration::from_secs(5)).await;
+        panic!("no pong within timeout");
+    };
+    let nodes = async {
+        nodes.await;
+        panic!("node exited");
+    };
+    block_on(or(or(rtt, nodes), timeout));
+}
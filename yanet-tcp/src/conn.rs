@@ -0,0 +1,103 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpStream},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_io::Async;
+use futures_lite::{AsyncRead, AsyncWriteExt};
+use serde::{Deserialize, Serialize};
+
+const MAX_FRAME: usize = 16 << 20;
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum Frame {
+    Hello(u16),
+    Data(Vec<u8>),
+}
+
+pub(crate) fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(ErrorKind::InvalidData, err)
+}
+
+pub(crate) fn encode(frame: &Frame) -> io::Result<Vec<u8>> {
+    let mut buf = postcard::to_extend(frame, vec![0u8; 4]).map_err(invalid_data)?;
+    let len = u32::try_from(buf.len() - 4).map_err(invalid_data)?;
+    buf[..4].copy_from_slice(&len.to_be_bytes());
+    Ok(buf)
+}
+
+pub(crate) struct Conn {
+    pub(crate) stream: Async<TcpStream>,
+    pub(crate) remote: SocketAddr,
+    buf: Vec<u8>,
+}
+
+impl Conn {
+    pub(crate) fn new(stream: Async<TcpStream>, remote: SocketAddr) -> Self {
+        Self {
+            stream,
+            remote,
+            buf: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        match self.stream.get_ref().peek(&mut [0u8; 1]) {
+            Ok(len) => len == 0,
+            Err(err) => err.kind() != ErrorKind::WouldBlock,
+        }
+    }
+
+    pub(crate) async fn write(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.stream.write_all(frame).await?;
+        self.stream.flush().await
+    }
+
+    pub(crate) fn poll_frames(
+        &mut self,
+        cx: &mut Context<'_>,
+        out: &mut Vec<Frame>,
+    ) -> Poll<io::Result<()>> {
+        let mut chunk = [0u8; 16 * 1024];
+        loop {
+            match Pin::new(&mut self.stream).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::UnexpectedEof.into())),
+                Poll::Ready(Ok(len)) => {
+                    self.buf.extend_from_slice(&chunk[..len]);
+                    self.parse(out)?;
+                    if !out.is_empty() {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn parse(&mut self, out: &mut Vec<Frame>) -> io::Result<()> {
+        let mut start = 0;
+        while self.buf.len() - start >= 4 {
+            let mut len = [0u8; 4];
+            len.copy_from_slice(&self.buf[start..start + 4]);
+            let len = u32::from_be_bytes(len) as usize;
+            if len > MAX_FRAME {
+                return Err(invalid_data("frame too large"));
+            }
+            let end = start + 4 + len;
+            if self.buf.len() < end {
+                break;
+            }
+            out.push(postcard::from_bytes(&self.buf[start + 4..end]).map_err(invalid_data)?);
+            start = end;
+        }
+        self.buf.drain(..start);
+        Ok(())
+    }
+}
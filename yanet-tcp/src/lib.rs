@@ -0,0 +1,207 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_io::Async;
+use serde::{de::DeserializeOwned, Serialize};
+use yanet_core::Socket;
+
+mod conn;
+
+pub type BroadcastError = yanet_core::BroadcastError<SocketAddr, io::Error>;
+
+use conn::{encode, invalid_data, Conn, Frame};
+
+pub struct Tcp {
+    peers: BTreeSet<SocketAddr>,
+    conns: Vec<Conn>,
+    pending: VecDeque<(Vec<u8>, SocketAddr)>,
+    connect_timeout: Duration,
+    listener: Async<TcpListener>,
+}
+
+impl Tcp {
+    pub fn new<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self {
+            peers: Default::default(),
+            conns: Vec::new(),
+            pending: VecDeque::new(),
+            connect_timeout: Duration::from_secs(5),
+            listener: Async::<TcpListener>::bind(first_addr(addr)?)?,
+        })
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.get_ref().local_addr()
+    }
+    pub fn add_peer<A: ToSocketAddrs>(&mut self, addr: A) -> io::Result<()> {
+        self.peers.extend(addr.to_socket_addrs()?);
+        Ok(())
+    }
+    pub fn remove_peer<A: ToSocketAddrs>(&mut self, addr: A) -> io::Result<bool> {
+        let mut removed = false;
+        for addr in addr.to_socket_addrs()? {
+            removed |= self.peers.remove(&addr);
+        }
+        Ok(removed)
+    }
+    pub fn peers(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.peers.iter().copied()
+    }
+    // Broadcast connects to peers one at a time, so an unresponsive peer
+    // delays every peer after it by up to this long.
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.connect_timeout = timeout;
+    }
+    pub fn connections(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.conns.iter().map(|conn| conn.remote)
+    }
+    async fn connect(&mut self, addr: SocketAddr) -> io::Result<usize> {
+        let timeout = async {
+            async_io::Timer::after(self.connect_timeout).await;
+            Err(io::Error::new(ErrorKind::TimedOut, "connect timed out"))
+        };
+        let stream = futures_lite::future::or(Async::<TcpStream>::connect(addr), timeout).await?;
+        stream.get_ref().set_nodelay(true)?;
+        let mut conn = Conn::new(stream, addr);
+        conn.write(&encode(&Frame::Hello(self.local_addr()?.port()))?)
+            .await?;
+        yanet_core::debug!(peer = %addr, "connected");
+        self.conns.push(conn);
+        Ok(self.conns.len() - 1)
+    }
+    async fn send_frame(&mut self, frame: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if let Some(i) = self.conns.iter().position(|conn| conn.remote == addr) {
+            if self.conns[i].is_closed() {
+                yanet_core::debug!(peer = %addr, "connection closed by peer, reconnecting");
+                self.conns.swap_remove(i);
+                return self.reconnect(frame, addr).await;
+            }
+            match self.conns[i].write(frame).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    yanet_core::debug!(peer = %addr, error = %err, "connection lost, reconnecting");
+                    self.conns.swap_remove(i);
+                }
+            }
+        }
+        self.reconnect(frame, addr).await
+    }
+    async fn reconnect(&mut self, frame: &[u8], addr: SocketAddr) -> io::Result<()> {
+        let i = self.connect(addr).await?;
+        let ret = self.conns[i].write(frame).await;
+        if ret.is_err() {
+            self.conns.swap_remove(i);
+        }
+        ret
+    }
+    fn accept(&mut self, cx: &mut Context<'_>) -> io::Result<bool> {
+        if self.listener.poll_readable(cx)?.is_pending() {
+            return Ok(false);
+        }
+        match self.listener.get_ref().accept() {
+            Ok((stream, addr)) => {
+                stream.set_nodelay(true)?;
+                yanet_core::debug!(peer = %addr, "accepted connection");
+                self.conns.push(Conn::new(Async::new(stream)?, addr));
+                Ok(true)
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+    fn poll_recv_raw(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<(Vec<u8>, SocketAddr)>> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Poll::Ready(Ok(item));
+            }
+            let mut progress = self.accept(cx)?;
+            let mut frames = Vec::new();
+            let mut i = 0;
+            while i < self.conns.len() {
+                let conn = &mut self.conns[i];
+                match conn.poll_frames(cx, &mut frames) {
+                    Poll::Ready(Ok(())) => {
+                        for frame in frames.drain(..) {
+                            match frame {
+                                Frame::Hello(port) => {
+                                    conn.remote = SocketAddr::new(conn.remote.ip(), port);
+                                    yanet_core::trace!(peer = %conn.remote, "hello received");
+                                }
+                                Frame::Data(dat) => {
+                                    yanet_core::trace!(
+                                        peer = %conn.remote,
+                                        len = dat.len(),
+                                        "received frame"
+                                    );
+                                    self.pending.push_back((dat, conn.remote));
+                                }
+                            }
+                        }
+                        progress = true;
+                        i += 1;
+                    }
+                    Poll::Ready(Err(err)) => {
+                        yanet_core::debug!(peer = %conn.remote, error = %err, "connection closed");
+                        self.conns.swap_remove(i);
+                    }
+                    Poll::Pending => i += 1,
+                }
+            }
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+fn first_addr<A: ToSocketAddrs>(addr: A) -> io::Result<SocketAddr> {
+    addr.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no address to bind"))
+}
+
+impl Socket for Tcp {
+    type Addr = SocketAddr;
+    type Error = io::Error;
+
+    async fn broadcast<D>(&mut self, data: &D) -> Result<(), Self::Error>
+    where
+        D: Serialize,
+    {
+        let frame = encode(&Frame::Data(
+            postcard::to_allocvec(data).map_err(invalid_data)?,
+        ))?;
+        let targets: BTreeSet<SocketAddr> = self.peers().chain(self.connections()).collect();
+        let mut failures = Vec::new();
+        for addr in targets {
+            if let Err(err) = self.send_frame(&frame, addr).await {
+                yanet_core::debug!(peer = %addr, error = %err, "failed to send frame");
+                failures.push((addr, err));
+            }
+        }
+        BroadcastError::check(failures).map_err(io::Error::other)
+    }
+    async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
+    where
+        D: Serialize + ?Sized,
+    {
+        let frame = encode(&Frame::Data(
+            postcard::to_allocvec(data).map_err(invalid_data)?,
+        ))?;
+        self.send_frame(&frame, addr).await
+    }
+    async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
+    where
+        D: DeserializeOwned,
+    {
+        let (dat, addr) = futures_lite::future::poll_fn(|cx| self.poll_recv_raw(cx)).await?;
+        Ok((postcard::from_bytes(&dat).map_err(invalid_data)?, addr))
+    }
+}
@@ -0,0 +1,104 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, TcpStream},
+    time::{Duration, Instant},
+};
+
+use futures_lite::future::block_on;
+use socket2::{Domain, Socket as RawSocket, Type};
+use yanet_core::Socket;
+use yanet_tcp::{BroadcastError, Tcp};
+
+// A listener whose accept queue is already full, so further SYNs go unanswered.
+fn unresponsive() -> (RawSocket, TcpStream, SocketAddr) {
+    let listener = RawSocket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    listener
+        .bind(&SocketAddr::from(([127, 0, 0, 1], 0)).into())
+        .unwrap();
+    listener.listen(0).unwrap();
+    let addr = listener.local_addr().unwrap().as_socket().unwrap();
+    let queued = TcpStream::connect(addr).unwrap();
+    (listener, queued, addr)
+}
+
+#[test]
+fn round_trip_over_one_connection() {
+    let mut a = Tcp::new("127.0.0.1:0").unwrap();
+    let mut b = Tcp::new("127.0.0.1:0").unwrap();
+    let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+    block_on(async {
+        a.send(&1u32, b_addr).await.unwrap();
+        // The sender is reported by its listening address, not its ephemeral port.
+        assert_eq!(b.recv::<u32>().await.unwrap(), (1, a_addr));
+        b.send("reply", a_addr).await.unwrap();
+        assert_eq!(a.recv::<String>().await.unwrap(), ("reply".into(), b_addr));
+    });
+    assert_eq!(a.connections().collect::<Vec<_>>(), vec![b_addr]);
+}
+
+#[test]
+fn reconnects_after_the_peer_restarts() {
+    let mut a = Tcp::new("127.0.0.1:0").unwrap();
+    let mut b = Tcp::new("127.0.0.1:0").unwrap();
+    let b_addr = b.local_addr().unwrap();
+    block_on(async {
+        a.send(&1u32, b_addr).await.unwrap();
+        b.recv::<u32>().await.unwrap();
+        drop(b);
+        let mut b = Tcp::new(b_addr).unwrap();
+        // The first write may still land in the dead connection's buffer.
+        let mut n = 2u32;
+        loop {
+            a.send(&n, b_addr).await.ok();
+            let recv = async { Some(b.recv::<u32>().await.unwrap().0) };
+            let wait = async {
+                async_io::Timer::after(Duration::from_millis(50)).await;
+                None
+            };
+            if let Some(got) = futures_lite::future::or(recv, wait).await {
+                assert!(got >= 2);
+                break;
+            }
+            n += 1;
+            assert!(n < 20, "never reconnected");
+        }
+    });
+}
+
+#[test]
+fn connect_times_out() {
+    let (_listener, _queued, addr) = unresponsive();
+    let mut a = Tcp::new("127.0.0.1:0").unwrap();
+    a.set_connect_timeout(Duration::from_millis(100));
+    let err = block_on(a.send(&1u32, addr)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+}
+
+#[test]
+fn unresponsive_peer_does_not_hold_up_broadcast() {
+    let (_listener, _queued, stuck) = unresponsive();
+    let mut a = Tcp::new("127.0.0.1:0").unwrap();
+    let mut b = Tcp::new("127.0.0.1:0").unwrap();
+    a.set_connect_timeout(Duration::from_millis(100));
+    a.add_peer(stuck).unwrap();
+    a.add_peer(b.local_addr().unwrap()).unwrap();
+    let start = Instant::now();
+    block_on(async {
+        let err = a.broadcast(&5u32).await.unwrap_err();
+        let report = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<BroadcastError>())
+            .expect("broadcast error");
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, stuck);
+        assert_eq!(b.recv::<u32>().await.unwrap().0, 5);
+    });
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        start.elapsed()
+    );
+}
@@ -0,0 +1,362 @@
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{BroadcastError, Udp};
+
+#[cfg(target_os = "linux")]
+const BATCH: usize = 16;
+const MAX_DATAGRAM: usize = u16::MAX as usize;
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::{
+        io, mem,
+        net::{SocketAddr, UdpSocket},
+        os::fd::AsRawFd,
+        ptr,
+    };
+
+    use socket2::SockAddr;
+
+    const UDP_SEGMENT: libc::c_int = 103;
+    const UDP_GRO: libc::c_int = 104;
+    pub(super) const MAX_SEGMENTS: usize = 64;
+
+    fn cvt(ret: libc::c_int) -> io::Result<usize> {
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(ret as usize)
+        }
+    }
+
+    pub(super) fn set_gro(socket: &UdpSocket, enable: bool) -> io::Result<()> {
+        let value = enable as libc::c_int;
+        cvt(unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_UDP,
+                UDP_GRO,
+                &value as *const _ as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        })
+        .map(drop)
+    }
+
+    pub(super) fn send_mmsg(socket: &UdpSocket, msgs: &[(&[u8], SocketAddr)]) -> io::Result<usize> {
+        let addrs: Vec<SockAddr> = msgs.iter().map(|(_, addr)| SockAddr::from(*addr)).collect();
+        let mut iovs: Vec<libc::iovec> = msgs
+            .iter()
+            .map(|(dat, _)| libc::iovec {
+                iov_base: dat.as_ptr() as *mut libc::c_void,
+                iov_len: dat.len(),
+            })
+            .collect();
+        let mut hdrs: Vec<libc::mmsghdr> = addrs
+            .iter()
+            .zip(iovs.iter_mut())
+            .map(|(addr, iov)| {
+                let mut hdr: libc::mmsghdr = unsafe { mem::zeroed() };
+                hdr.msg_hdr.msg_name = addr.as_ptr() as *mut libc::c_void;
+                hdr.msg_hdr.msg_namelen = addr.len();
+                hdr.msg_hdr.msg_iov = iov;
+                hdr.msg_hdr.msg_iovlen = 1;
+                hdr
+            })
+            .collect();
+        cvt(unsafe { libc::sendmmsg(socket.as_raw_fd(), hdrs.as_mut_ptr(), hdrs.len() as _, 0) })
+    }
+
+    pub(super) fn send_gso(
+        socket: &UdpSocket,
+        dat: &[u8],
+        segment: u16,
+        addr: SocketAddr,
+    ) -> io::Result<usize> {
+        let addr = SockAddr::from(addr);
+        let mut iov = libc::iovec {
+            iov_base: dat.as_ptr() as *mut libc::c_void,
+            iov_len: dat.len(),
+        };
+        let mut control = [0u64; 4];
+        let mut hdr: libc::msghdr = unsafe { mem::zeroed() };
+        hdr.msg_name = addr.as_ptr() as *mut libc::c_void;
+        hdr.msg_namelen = addr.len();
+        hdr.msg_iov = &mut iov;
+        hdr.msg_iovlen = 1;
+        hdr.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        hdr.msg_controllen = unsafe { libc::CMSG_SPACE(mem::size_of::<u16>() as u32) } as _;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&hdr);
+            (*cmsg).cmsg_level = libc::SOL_UDP;
+            (*cmsg).cmsg_type = UDP_SEGMENT;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as u32) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, segment);
+        }
+        let ret = unsafe { libc::sendmsg(socket.as_raw_fd(), &hdr, 0) };
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(ret as usize)
+        }
+    }
+
+    pub(super) fn recv_mmsg(
+        socket: &UdpSocket,
+        bufs: &mut [Vec<u8>],
+        out: &mut Vec<(Vec<u8>, SocketAddr)>,
+    ) -> io::Result<usize> {
+        let mut names: Vec<libc::sockaddr_storage> = vec![unsafe { mem::zeroed() }; bufs.len()];
+        let mut controls = vec![[0u64; 4]; bufs.len()];
+        let mut iovs: Vec<libc::iovec> = bufs
+            .iter_mut()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+                iov_len: buf.len(),
+            })
+            .collect();
+        let mut hdrs: Vec<libc::mmsghdr> = names
+            .iter_mut()
+            .zip(controls.iter_mut())
+            .zip(iovs.iter_mut())
+            .map(|((name, control), iov)| {
+                let mut hdr: libc::mmsghdr = unsafe { mem::zeroed() };
+                hdr.msg_hdr.msg_name = name as *mut _ as *mut libc::c_void;
+                hdr.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as _;
+                hdr.msg_hdr.msg_iov = iov;
+                hdr.msg_hdr.msg_iovlen = 1;
+                hdr.msg_hdr.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+                hdr.msg_hdr.msg_controllen = mem::size_of_val(control) as _;
+                hdr
+            })
+            .collect();
+        let count = cvt(unsafe {
+            libc::recvmmsg(
+                socket.as_raw_fd(),
+                hdrs.as_mut_ptr(),
+                hdrs.len() as _,
+                0,
+                ptr::null_mut(),
+            )
+        })?;
+        for (i, hdr) in hdrs.iter().take(count).enumerate() {
+            if hdr.msg_hdr.msg_flags & libc::MSG_TRUNC != 0 {
+                yanet_core::debug!("dropping truncated datagram");
+                continue;
+            }
+            let addr = unsafe { SockAddr::new(names[i], hdr.msg_hdr.msg_namelen) };
+            let Some(addr) = addr.as_socket() else {
+                continue;
+            };
+            let len = hdr.msg_len as usize;
+            let segment = unsafe { gro_segment(&hdr.msg_hdr) }.unwrap_or(len).max(1);
+            for chunk in bufs[i][..len].chunks(segment) {
+                out.push((chunk.to_vec(), addr));
+            }
+        }
+        Ok(count)
+    }
+
+    unsafe fn gro_segment(hdr: &libc::msghdr) -> Option<usize> {
+        let mut cmsg = libc::CMSG_FIRSTHDR(hdr);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_UDP && (*cmsg).cmsg_type == UDP_GRO {
+                let size = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+                return Some(size as usize);
+            }
+            cmsg = libc::CMSG_NXTHDR(hdr, cmsg);
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Batch {
+    pending: VecDeque<(Vec<u8>, SocketAddr)>,
+    bufs: Vec<Vec<u8>>,
+    no_gso: bool,
+}
+
+impl Udp {
+    pub(crate) fn enable_offload(&self) {
+        #[cfg(target_os = "linux")]
+        if sys::set_gro(self.inner.get_ref(), true).is_err() {
+            yanet_core::debug!("UDP GRO unavailable");
+        }
+    }
+
+    pub async fn send_batch<'a, D, I>(&mut self, items: I) -> io::Result<()>
+    where
+        D: Serialize + ?Sized + 'a,
+        I: IntoIterator<Item = (&'a D, SocketAddr)>,
+    {
+        let mut msgs = Vec::new();
+        for (data, addr) in items {
+            let dat = postcard::to_allocvec(data)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+            msgs.push((dat, addr));
+        }
+        let msgs: Vec<(&[u8], SocketAddr)> = msgs
+            .iter()
+            .map(|(dat, addr)| (dat.as_slice(), *addr))
+            .collect();
+        BroadcastError::check(self.send_raw_batch(&msgs).await).map_err(io::Error::other)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) async fn send_raw_batch(
+        &mut self,
+        msgs: &[(&[u8], SocketAddr)],
+    ) -> Vec<(SocketAddr, io::Error)> {
+        let mut failures = Vec::new();
+        let mut origs = Vec::with_capacity(msgs.len());
+        let mut mapped = Vec::with_capacity(msgs.len());
+        for (dat, addr) in msgs {
+            match self.outgoing(*addr) {
+                Ok(out) => {
+                    origs.push(*addr);
+                    mapped.push((*dat, out));
+                }
+                Err(err) => failures.push((*addr, err)),
+            }
+        }
+        let mut msgs = mapped;
+        if !self.batch.no_gso && msgs.len() > 1 && msgs.len() <= sys::MAX_SEGMENTS {
+            let (first, addr) = msgs[0];
+            let segment = first.len();
+            let uniform = msgs.iter().enumerate().all(|(i, (dat, a))| {
+                *a == addr
+                    && (dat.len() == segment || (i == msgs.len() - 1 && dat.len() <= segment))
+            });
+            let total: usize = msgs.iter().map(|(dat, _)| dat.len()).sum();
+            if uniform && segment > 0 && total <= MAX_DATAGRAM - 64 {
+                let dat = msgs
+                    .iter()
+                    .flat_map(|(dat, _)| dat.iter().copied())
+                    .collect::<Vec<_>>();
+                match self
+                    .inner
+                    .write_with(|s| sys::send_gso(s, &dat, segment as u16, addr))
+                    .await
+                {
+                    Ok(_) => {
+                        yanet_core::trace!(peer = %addr, segments = msgs.len(), "sent segmented datagram");
+                        return failures;
+                    }
+                    Err(err)
+                        if matches!(
+                            err.raw_os_error(),
+                            Some(libc::EIO | libc::EINVAL | libc::ENOPROTOOPT)
+                        ) =>
+                    {
+                        yanet_core::debug!(error = %err, "UDP GSO unavailable, falling back to sendmmsg");
+                        self.batch.no_gso = true;
+                    }
+                    Err(err) => {
+                        failures.push((origs[0], err));
+                        return failures;
+                    }
+                }
+            }
+        }
+        while !msgs.is_empty() {
+            match self.inner.write_with(|s| sys::send_mmsg(s, &msgs)).await {
+                Ok(sent) => {
+                    yanet_core::trace!(count = sent, "sent datagram batch");
+                    msgs.drain(..sent);
+                    origs.drain(..sent);
+                }
+                Err(err) => {
+                    yanet_core::debug!(peer = %origs[0], error = %err, "failed to send datagram");
+                    msgs.remove(0);
+                    failures.push((origs.remove(0), err));
+                }
+            }
+        }
+        failures
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) async fn send_raw_batch(
+        &mut self,
+        msgs: &[(&[u8], SocketAddr)],
+    ) -> Vec<(SocketAddr, io::Error)> {
+        let mut failures = Vec::new();
+        for (dat, addr) in msgs {
+            if let Err(err) = self.send_raw(dat, *addr).await {
+                yanet_core::debug!(peer = %addr, error = %err, "failed to send datagram");
+                failures.push((*addr, err));
+            }
+        }
+        failures
+    }
+
+    pub async fn recv_batch<D>(&mut self, max: usize) -> io::Result<Vec<(D, SocketAddr)>>
+    where
+        D: DeserializeOwned,
+    {
+        self.fill().await?;
+        let count = max.min(self.batch.pending.len());
+        Ok(self
+            .batch
+            .pending
+            .drain(..count)
+            .filter_map(|(dat, addr)| match postcard::from_bytes(&dat) {
+                Ok(data) => Some((data, addr)),
+                Err(_) => {
+                    yanet_core::debug!(peer = %addr, "dropping undecodable datagram");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    pub(crate) async fn recv_raw(&mut self) -> io::Result<(Vec<u8>, SocketAddr)> {
+        self.fill().await?;
+        self.batch
+            .pending
+            .pop_front()
+            .ok_or_else(|| io::Error::from(ErrorKind::WouldBlock))
+    }
+
+    async fn fill(&mut self) -> io::Result<()> {
+        while self.batch.pending.is_empty() {
+            let mut out = Vec::new();
+            self.recv_into(&mut out).await?;
+            for (dat, addr) in out {
+                let addr = Self::incoming(addr);
+                yanet_core::trace!(peer = %addr, len = dat.len(), "received datagram");
+                self.peers.learn(addr);
+                self.batch.pending.push_back((dat, addr));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn recv_into(&mut self, out: &mut Vec<(Vec<u8>, SocketAddr)>) -> io::Result<()> {
+        if self.batch.bufs.is_empty() {
+            self.batch.bufs = vec![vec![0u8; MAX_DATAGRAM]; BATCH];
+        }
+        let bufs = &mut self.batch.bufs;
+        self.inner
+            .read_with(|s| sys::recv_mmsg(s, bufs, out))
+            .await
+            .map(drop)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn recv_into(&mut self, out: &mut Vec<(Vec<u8>, SocketAddr)>) -> io::Result<()> {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        let (len, addr) = self.inner.recv_from(&mut buf).await?;
+        buf.truncate(len);
+        out.push((buf, addr));
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::Udp;
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+pub struct UdpBuilder {
+    addr: io::Result<SocketAddr>,
+    dual_stack: bool,
+    reuse_address: bool,
+    reuse_port: bool,
+    recv_buffer: Option<usize>,
+    send_buffer: Option<usize>,
+    ttl: Option<u32>,
+    multicast_ttl: Option<u32>,
+    multicast_loop: Option<bool>,
+    tos: Option<u32>,
+    device: Option<String>,
+}
+
+impl UdpBuilder {
+    pub(crate) fn new<A: ToSocketAddrs>(addr: A) -> Self {
+        let addr = addr
+            .to_socket_addrs()
+            .and_then(|mut addrs| addrs.next().ok_or_else(|| invalid("no address to bind")));
+        Self {
+            addr,
+            dual_stack: false,
+            reuse_address: false,
+            reuse_port: false,
+            recv_buffer: None,
+            send_buffer: None,
+            ttl: None,
+            multicast_ttl: None,
+            multicast_loop: None,
+            tos: None,
+            device: None,
+        }
+    }
+
+    pub fn dual_stack(mut self, enable: bool) -> Self {
+        self.dual_stack = enable;
+        self
+    }
+
+    pub fn reuse_address(mut self, enable: bool) -> Self {
+        self.reuse_address = enable;
+        self
+    }
+
+    pub fn reuse_port(mut self, enable: bool) -> Self {
+        self.reuse_port = enable;
+        self
+    }
+
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer = Some(size);
+        self
+    }
+
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer = Some(size);
+        self
+    }
+
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn multicast_ttl(mut self, ttl: u32) -> Self {
+        self.multicast_ttl = Some(ttl);
+        self
+    }
+
+    pub fn multicast_loop(mut self, enable: bool) -> Self {
+        self.multicast_loop = Some(enable);
+        self
+    }
+
+    pub fn tos(mut self, tos: u32) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    pub fn dscp(self, dscp: u8) -> Self {
+        self.tos(u32::from(dscp) << 2)
+    }
+
+    pub fn bind_device(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    fn validate(&self, addr: &SocketAddr) -> io::Result<()> {
+        if self.dual_stack && addr.is_ipv4() {
+            return Err(invalid("dual-stack requires an IPv6 bind address"));
+        }
+        if self.recv_buffer == Some(0) || self.send_buffer == Some(0) {
+            return Err(invalid("buffer size must be non-zero"));
+        }
+        if matches!(self.ttl, Some(ttl) if ttl == 0 || ttl > 255) {
+            return Err(invalid("ttl must be between 1 and 255"));
+        }
+        if matches!(self.multicast_ttl, Some(ttl) if ttl > 255) {
+            return Err(invalid("multicast ttl must be at most 255"));
+        }
+        if matches!(self.tos, Some(tos) if tos > 255) {
+            return Err(invalid("tos must fit in one byte"));
+        }
+        if let Some(device) = &self.device {
+            if device.is_empty() || device.len() > 15 || device.contains('\0') {
+                return Err(invalid("invalid device name"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> io::Result<Udp> {
+        let addr = self
+            .addr
+            .as_ref()
+            .map_err(|e| io::Error::new(e.kind(), e.to_string()))?;
+        let addr = *addr;
+        self.validate(&addr)?;
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+        if addr.is_ipv6() {
+            socket.set_only_v6(!self.dual_stack)?;
+        }
+        socket.set_reuse_address(self.reuse_address)?;
+        if self.reuse_port {
+            #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+            socket.set_reuse_port(true)?;
+            #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+            return Err(invalid("SO_REUSEPORT is not supported on this platform"));
+        }
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer {
+            socket.set_send_buffer_size(size)?;
+        }
+        match addr {
+            SocketAddr::V4(_) => {
+                if let Some(ttl) = self.ttl {
+                    socket.set_ttl(ttl)?;
+                }
+                if let Some(ttl) = self.multicast_ttl {
+                    socket.set_multicast_ttl_v4(ttl)?;
+                }
+                if let Some(enable) = self.multicast_loop {
+                    socket.set_multicast_loop_v4(enable)?;
+                }
+                if let Some(tos) = self.tos {
+                    socket.set_tos(tos)?;
+                }
+            }
+            SocketAddr::V6(_) => {
+                if let Some(hops) = self.ttl {
+                    socket.set_unicast_hops_v6(hops)?;
+                }
+                if let Some(hops) = self.multicast_ttl {
+                    socket.set_multicast_hops_v6(hops)?;
+                }
+                if let Some(enable) = self.multicast_loop {
+                    socket.set_multicast_loop_v6(enable)?;
+                }
+                if let Some(tos) = self.tos {
+                    set_tclass_v6(&socket, tos)?;
+                }
+            }
+        }
+        if let Some(device) = &self.device {
+            #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+            socket.bind_device(Some(device.as_bytes()))?;
+            #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+            return Err(invalid(
+                "binding to a device is not supported on this platform",
+            ));
+        }
+        socket.bind(&addr.into())?;
+        Udp::from_std(socket.into())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_tclass_v6(socket: &Socket, tclass: u32) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let value = tclass as libc::c_int;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::IPV6_TCLASS,
+            &value as *const _ as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_tclass_v6(_socket: &Socket, _tclass: u32) -> io::Result<()> {
+    Err(invalid("traffic class is not supported on this platform"))
+}
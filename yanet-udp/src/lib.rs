@@ -3,53 +3,164 @@
 
 use std::{
     fmt::Debug,
-    io::{self, Error, ErrorKind, Result},
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket},
+    io::{self, Error, ErrorKind},
+    net::{
+        IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs,
+        UdpSocket,
+    },
     time::Duration,
 };
 
+use async_io::Async;
 use serde::{de::DeserializeOwned, Serialize};
 use yanet_core::Socket;
 
-pub async fn try_async<T>(mut f: impl FnMut() -> Result<T>) -> Result<T> {
-    loop {
-        match f() {
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                futures_timer::Delay::new(Duration::from_millis(100)).await;
-            }
-            res => return res,
-        }
+mod batch;
+mod builder;
+mod multicast;
+mod peers;
+pub use builder::UdpBuilder;
+pub use multicast::{interfaces, Interface};
+
+pub type BroadcastError = yanet_core::BroadcastError<SocketAddr, Error>;
+
+fn is_link_local(addr: &Ipv6Addr) -> bool {
+    let segment = addr.segments()[0];
+    if addr.is_multicast() {
+        matches!(segment & 0x000f, 0x1 | 0x2)
+    } else {
+        segment & 0xffc0 == 0xfe80
     }
 }
 
 pub struct Udp {
-    peers: Vec<SocketAddr>,
-    inner: UdpSocket,
+    peers: peers::Peers,
+    groups: multicast::Groups,
+    batch: batch::Batch,
+    inner: Async<UdpSocket>,
 }
 
 impl Udp {
     pub fn new<A: ToSocketAddrs + Debug>(addr: A) -> io::Result<Self> {
         let socket = UdpSocket::bind(&addr)?;
-        socket.set_nonblocking(true)?;
-        Ok(Self {
+        Self::from_std(socket)
+    }
+    pub fn new_dual_stack(port: u16) -> io::Result<Self> {
+        Self::builder((Ipv6Addr::UNSPECIFIED, port))
+            .dual_stack(true)
+            .build()
+    }
+    pub fn builder<A: ToSocketAddrs>(addr: A) -> UdpBuilder {
+        UdpBuilder::new(addr)
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().local_addr()
+    }
+    fn from_std(socket: UdpSocket) -> io::Result<Self> {
+        let udp = Self {
             peers: Default::default(),
-            inner: socket,
-        })
+            groups: Default::default(),
+            batch: Default::default(),
+            inner: Async::new(socket)?,
+        };
+        udp.enable_offload();
+        Ok(udp)
     }
     pub fn join_multicast_v4(
         &mut self,
         multicast: &Ipv4Addr,
         interface: &Ipv4Addr,
     ) -> io::Result<()> {
+        self.peers.insert(
+            SocketAddrV4::new(*multicast, self.inner.get_ref().local_addr()?.port()).into(),
+        );
+        self.inner
+            .get_ref()
+            .join_multicast_v4(multicast, interface)?;
+        yanet_core::info!(%multicast, %interface, "joined multicast group");
+        Ok(())
+    }
+    pub fn join_multicast_v6(&mut self, multicast: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        let port = self.inner.get_ref().local_addr()?.port();
+        let scope = if is_link_local(multicast) {
+            interface
+        } else {
+            0
+        };
         self.peers
-            .push(SocketAddrV4::new(multicast.clone(), self.inner.local_addr()?.port()).into());
-        self.inner.join_multicast_v4(multicast, interface)?;
+            .insert(SocketAddrV6::new(*multicast, port, 0, scope).into());
+        self.inner
+            .get_ref()
+            .join_multicast_v6(multicast, interface)?;
+        yanet_core::info!(%multicast, interface, "joined multicast group");
         Ok(())
     }
     pub fn add_peer<A: ToSocketAddrs>(&mut self, addr: A) -> io::Result<()> {
-        addr.to_socket_addrs()?.for_each(|a| self.peers.push(a));
+        for addr in Self::peer_addrs(addr)? {
+            self.peers.insert(addr);
+        }
         Ok(())
     }
+    pub fn add_peer_with_ttl<A: ToSocketAddrs>(
+        &mut self,
+        addr: A,
+        ttl: Duration,
+    ) -> io::Result<()> {
+        for addr in Self::peer_addrs(addr)? {
+            self.peers.insert_expiring(addr, ttl);
+        }
+        Ok(())
+    }
+    pub fn remove_peer<A: ToSocketAddrs>(&mut self, addr: A) -> io::Result<bool> {
+        let mut removed = false;
+        for addr in addr.to_socket_addrs()? {
+            removed |= self.peers.remove(&addr);
+        }
+        Ok(removed)
+    }
+    pub fn peers(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.peers.iter()
+    }
+    pub fn learn_peers(&mut self, ttl: Option<Duration>) {
+        self.peers.set_learn(ttl);
+    }
+    fn peer_addrs<A: ToSocketAddrs>(addr: A) -> io::Result<Vec<SocketAddr>> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        for addr in addrs.iter() {
+            match addr {
+                SocketAddr::V6(v6) if is_link_local(v6.ip()) && v6.scope_id() == 0 => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "link-local peer address requires a scope id",
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(addrs)
+    }
+    fn outgoing(&self, addr: SocketAddr) -> io::Result<SocketAddr> {
+        Ok(match (self.inner.get_ref().local_addr()?, addr) {
+            (SocketAddr::V6(_), SocketAddr::V4(v4)) => {
+                SocketAddrV6::new(v4.ip().to_ipv6_mapped(), v4.port(), 0, 0).into()
+            }
+            _ => addr,
+        })
+    }
+    async fn send_raw(&self, dat: &[u8], addr: SocketAddr) -> io::Result<()> {
+        yanet_core::trace!(peer = %addr, len = dat.len(), "sending datagram");
+        self.inner.send_to(dat, self.outgoing(addr)?).await?;
+        Ok(())
+    }
+    fn incoming(addr: SocketAddr) -> SocketAddr {
+        match addr {
+            SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+                Some(v4) => SocketAddr::new(IpAddr::V4(v4), v6.port()),
+                None => addr,
+            },
+            addr => addr,
+        }
+    }
 }
 impl Socket for Udp {
     type Addr = SocketAddr;
@@ -59,10 +170,20 @@ impl Socket for Udp {
     where
         D: Serialize,
     {
-        for addr in self.peers.clone().iter() {
-            self.send(&data, *addr).await?;
+        let dat =
+            postcard::to_allocvec(data).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        if let Err(err) = self.refresh_multicast_if_due() {
+            yanet_core::warn!(error = %err, "failed to refresh multicast interfaces");
         }
-        Ok(())
+        self.peers.prune();
+        let msgs: Vec<(&[u8], SocketAddr)> = self
+            .peers
+            .iter()
+            .map(|addr| (dat.as_slice(), addr))
+            .collect();
+        let mut failures = self.send_raw_batch(&msgs).await;
+        failures.extend(self.send_multicast(&dat).await);
+        BroadcastError::check(failures).map_err(io::Error::other)
     }
     async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> std::result::Result<(), Self::Error>
     where
@@ -70,18 +191,30 @@ impl Socket for Udp {
     {
         let dat =
             postcard::to_allocvec(data).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
-        try_async(|| self.inner.send_to(&dat, addr)).await?;
-        Ok(())
+        self.send_raw(&dat, addr).await
     }
     async fn recv<D>(&mut self) -> std::result::Result<(D, Self::Addr), Self::Error>
     where
         D: DeserializeOwned,
     {
-        let mut buf = [0u8; 1024];
-        let (len, addr) = try_async(|| self.inner.recv_from(&mut buf)).await?;
+        let (dat, addr) = loop {
+            if let Err(err) = self.refresh_multicast_if_due() {
+                yanet_core::warn!(error = %err, "failed to refresh multicast interfaces");
+            }
+            let Some(due) = self.multicast_refresh_at() else {
+                break self.recv_raw().await?;
+            };
+            let recv = async { Some(self.recv_raw().await) };
+            let refresh = async {
+                async_io::Timer::at(due).await;
+                None
+            };
+            if let Some(received) = futures_micro::or!(recv, refresh).await {
+                break received?;
+            }
+        };
         Ok((
-            postcard::from_bytes(&buf[..len])
-                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?,
+            postcard::from_bytes(&dat).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?,
             addr,
         ))
     }
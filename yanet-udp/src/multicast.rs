@@ -0,0 +1,228 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr, SocketAddrV6, UdpSocket},
+    time::{Duration, Instant},
+};
+
+pub use if_addrs::Interface;
+use socket2::SockRef;
+
+use crate::{is_link_local, Udp};
+
+pub fn interfaces() -> io::Result<Vec<Interface>> {
+    if_addrs::get_if_addrs()
+}
+
+type Filter = Box<dyn Fn(&Interface) -> bool + Send + Sync>;
+
+struct Group {
+    addr: IpAddr,
+    filter: Filter,
+    joined: Vec<Interface>,
+}
+
+impl Group {
+    fn wants(&self, interface: &Interface) -> bool {
+        self.addr.is_ipv4() == interface.ip().is_ipv4() && (self.filter)(interface)
+    }
+
+    // if_addrs lists one entry per address, but membership is per interface.
+    fn has_joined(&self, interface: &Interface) -> bool {
+        self.joined
+            .iter()
+            .any(|joined| match (joined.index, interface.index) {
+                (Some(a), Some(b)) => a == b,
+                _ => joined.name == interface.name,
+            })
+    }
+
+    fn target(&self, port: u16, interface: &Interface) -> SocketAddr {
+        match self.addr {
+            IpAddr::V6(addr) if is_link_local(&addr) => {
+                SocketAddrV6::new(addr, port, 0, interface.index.unwrap_or(0)).into()
+            }
+            addr => SocketAddr::new(addr, port),
+        }
+    }
+}
+
+pub(crate) struct Groups {
+    groups: Vec<Group>,
+    interval: Duration,
+    refreshed: Instant,
+}
+
+impl Default for Groups {
+    fn default() -> Self {
+        Self {
+            groups: Vec::new(),
+            interval: Duration::from_secs(30),
+            refreshed: Instant::now(),
+        }
+    }
+}
+
+fn join(socket: &UdpSocket, group: IpAddr, interface: &Interface) -> io::Result<()> {
+    match (group, interface.ip(), interface.index) {
+        (IpAddr::V4(group), IpAddr::V4(ip), _) => socket.join_multicast_v4(&group, &ip),
+        (IpAddr::V6(group), IpAddr::V6(_), Some(index)) => socket.join_multicast_v6(&group, index),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "interface cannot join multicast group",
+        )),
+    }
+}
+
+fn leave(socket: &UdpSocket, group: IpAddr, interface: &Interface) -> io::Result<()> {
+    match (group, interface.ip(), interface.index) {
+        (IpAddr::V4(group), IpAddr::V4(ip), _) => socket.leave_multicast_v4(&group, &ip),
+        (IpAddr::V6(group), IpAddr::V6(_), Some(index)) => socket.leave_multicast_v6(&group, index),
+        _ => Ok(()),
+    }
+}
+
+fn select(socket: &UdpSocket, interface: &Interface) -> io::Result<()> {
+    match (interface.ip(), interface.index) {
+        (IpAddr::V4(ip), _) => SockRef::from(socket).set_multicast_if_v4(&ip),
+        (IpAddr::V6(_), Some(index)) => SockRef::from(socket).set_multicast_if_v6(index),
+        _ => Ok(()),
+    }
+}
+
+impl Udp {
+    pub fn join_multicast_all(&mut self, group: IpAddr) -> io::Result<()> {
+        self.join_multicast_on(group, |interface| !interface.is_loopback())
+    }
+
+    pub fn join_multicast_on(
+        &mut self,
+        group: IpAddr,
+        filter: impl Fn(&Interface) -> bool + Send + Sync + 'static,
+    ) -> io::Result<()> {
+        if !group.is_multicast() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not a multicast address",
+            ));
+        }
+        self.groups.groups.push(Group {
+            addr: group,
+            filter: Box::new(filter),
+            joined: Vec::new(),
+        });
+        self.refresh_multicast()
+    }
+
+    pub fn set_multicast_refresh(&mut self, interval: Duration) {
+        self.groups.interval = interval;
+    }
+
+    pub fn multicast_interfaces(&self) -> impl Iterator<Item = (IpAddr, &Interface)> {
+        self.groups
+            .groups
+            .iter()
+            .flat_map(|group| group.joined.iter().map(move |i| (group.addr, i)))
+    }
+
+    pub fn refresh_multicast(&mut self) -> io::Result<()> {
+        self.groups.refreshed = Instant::now();
+        let interfaces = interfaces()?;
+        let socket = self.inner.get_ref();
+        for group in self.groups.groups.iter_mut() {
+            group.joined.retain(|interface| {
+                let present = interfaces.contains(interface);
+                if !present {
+                    leave(socket, group.addr, interface).ok();
+                    yanet_core::info!(
+                        group = %group.addr,
+                        interface = %interface.name,
+                        "left multicast group"
+                    );
+                }
+                present
+            });
+            for interface in interfaces.iter() {
+                if group.has_joined(interface) || !group.wants(interface) {
+                    continue;
+                }
+                match join(socket, group.addr, interface) {
+                    Ok(()) => {
+                        yanet_core::info!(
+                            group = %group.addr,
+                            interface = %interface.name,
+                            "joined multicast group"
+                        );
+                        group.joined.push(interface.clone());
+                    }
+                    Err(err) => {
+                        yanet_core::warn!(
+                            group = %group.addr,
+                            interface = %interface.name,
+                            error = %err,
+                            "failed to join multicast group"
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn refresh_multicast_if_due(&mut self) -> io::Result<()> {
+        if self
+            .multicast_refresh_at()
+            .is_some_and(|due| due <= Instant::now())
+        {
+            self.refresh_multicast()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn multicast_refresh_at(&self) -> Option<Instant> {
+        if self.groups.groups.is_empty() {
+            return None;
+        }
+        Some(self.groups.refreshed + self.groups.interval)
+    }
+
+    pub(crate) async fn send_multicast(&self, dat: &[u8]) -> Vec<(SocketAddr, io::Error)> {
+        let socket = self.inner.get_ref();
+        let mut failures = Vec::new();
+        let port = match socket.local_addr() {
+            Ok(addr) => addr.port(),
+            Err(err) => {
+                // Without a port there is no target, so report every group.
+                for group in self.groups.groups.iter() {
+                    let err = io::Error::new(err.kind(), err.to_string());
+                    failures.push((SocketAddr::new(group.addr, 0), err));
+                }
+                return failures;
+            }
+        };
+        for group in self.groups.groups.iter() {
+            for interface in group.joined.iter() {
+                let target = group.target(port, interface);
+                if let Err(err) = select(socket, interface) {
+                    failures.push((target, err));
+                    continue;
+                }
+                yanet_core::trace!(
+                    group = %target,
+                    interface = %interface.name,
+                    len = dat.len(),
+                    "sending multicast datagram"
+                );
+                if let Err(err) = self.inner.send_to(dat, target).await {
+                    yanet_core::debug!(
+                        group = %target,
+                        interface = %interface.name,
+                        error = %err,
+                        "failed to send multicast datagram"
+                    );
+                    failures.push((target, err));
+                }
+            }
+        }
+        failures
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+pub(crate) struct Peers {
+    peers: BTreeMap<SocketAddr, Option<Instant>>,
+    learn: Option<Duration>,
+}
+
+impl Peers {
+    pub(crate) fn insert(&mut self, addr: SocketAddr) {
+        self.peers.insert(addr, None);
+    }
+
+    pub(crate) fn insert_expiring(&mut self, addr: SocketAddr, ttl: Duration) {
+        let expiry = Instant::now() + ttl;
+        match self.peers.get_mut(&addr) {
+            Some(None) => {}
+            Some(Some(current)) => *current = (*current).max(expiry),
+            None => {
+                yanet_core::debug!(peer = %addr, "peer added");
+                self.peers.insert(addr, Some(expiry));
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, addr: &SocketAddr) -> bool {
+        self.peers.remove(addr).is_some()
+    }
+
+    pub(crate) fn set_learn(&mut self, ttl: Option<Duration>) {
+        self.learn = ttl;
+    }
+
+    pub(crate) fn learn(&mut self, addr: SocketAddr) {
+        if let Some(ttl) = self.learn {
+            self.insert_expiring(addr, ttl);
+        }
+    }
+
+    pub(crate) fn prune(&mut self) {
+        let now = Instant::now();
+        self.peers.retain(|addr, expiry| match expiry {
+            Some(expiry) if *expiry <= now => {
+                yanet_core::debug!(peer = %addr, "peer expired");
+                false
+            }
+            _ => true,
+        });
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        let now = Instant::now();
+        self.peers
+            .iter()
+            .filter(move |(_, expiry)| expiry.is_none_or(|expiry| expiry > now))
+            .map(|(addr, _)| *addr)
+    }
+}
@@ -0,0 +1,75 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use futures_lite::future::block_on;
+use yanet_core::Socket;
+use yanet_udp::Udp;
+
+async fn recv_all(udp: &mut Udp, count: usize) -> Vec<u64> {
+    let mut received = Vec::new();
+    while received.len() < count {
+        let batch = udp.recv_batch::<u64>(count - received.len()).await.unwrap();
+        received.extend(batch.into_iter().map(|(n, _)| n));
+    }
+    received
+}
+
+#[test]
+fn segmented_batch_to_one_peer_arrives_as_separate_datagrams() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let b_addr = b.local_addr().unwrap();
+    // Fixed-width values give every datagram the same size.
+    let items: Vec<u64> = (0..16).map(|n| u64::MAX - n).collect();
+    block_on(async {
+        a.send_batch(items.iter().map(|n| (n, b_addr)))
+            .await
+            .unwrap();
+        assert_eq!(recv_all(&mut b, items.len()).await, items);
+    });
+}
+
+#[test]
+fn mixed_batch_reaches_every_peer() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let mut c = Udp::new("127.0.0.1:0").unwrap();
+    let (b_addr, c_addr) = (b.local_addr().unwrap(), c.local_addr().unwrap());
+    let items = [(&1u64, b_addr), (&2, c_addr), (&300, b_addr), (&4, c_addr)];
+    block_on(async {
+        a.send_batch(items).await.unwrap();
+        assert_eq!(recv_all(&mut b, 2).await, vec![1, 300]);
+        assert_eq!(recv_all(&mut c, 2).await, vec![2, 4]);
+    });
+}
+
+#[test]
+fn recv_batch_keeps_the_rest_for_later() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let b_addr = b.local_addr().unwrap();
+    block_on(async {
+        for n in 0..4u64 {
+            a.send(&n, b_addr).await.unwrap();
+        }
+        let mut received = Vec::new();
+        while received.len() < 4 {
+            let batch = b.recv_batch::<u64>(1).await.unwrap();
+            assert!(batch.len() <= 1);
+            received.extend(batch.into_iter().map(|(n, _)| n));
+        }
+        assert_eq!(received, vec![0, 1, 2, 3]);
+    });
+}
+
+#[test]
+fn recv_batch_skips_undecodable_datagrams() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let b_addr = b.local_addr().unwrap();
+    block_on(async {
+        a.send(&[0xffu8; 12], b_addr).await.unwrap();
+        a.send(&7u64, b_addr).await.unwrap();
+        assert_eq!(recv_all(&mut b, 1).await, vec![7]);
+    });
+}
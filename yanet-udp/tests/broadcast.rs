@@ -0,0 +1,29 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::net::SocketAddr;
+
+use futures_lite::future::block_on;
+use yanet_core::Socket;
+use yanet_udp::{BroadcastError, Udp};
+
+#[test]
+fn broadcast_reaches_peers_after_a_failure() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let b_addr = b.local_addr().unwrap();
+    // Port zero cannot be sent to, and sorts before the real peer.
+    let bad = SocketAddr::from(([127, 0, 0, 1], 0));
+    a.add_peer(bad).unwrap();
+    a.add_peer(b_addr).unwrap();
+    block_on(async {
+        let err = a.broadcast(&3u32).await.unwrap_err();
+        let report = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<BroadcastError>())
+            .expect("broadcast error");
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, bad);
+        assert_eq!(b.recv::<u32>().await.unwrap().0, 3);
+    });
+}
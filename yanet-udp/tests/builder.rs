@@ -0,0 +1,54 @@
+use std::io::ErrorKind;
+
+use yanet_udp::Udp;
+
+fn rejected(builder: yanet_udp::UdpBuilder) {
+    let err = builder.build().err().expect("builder accepted bad options");
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn invalid_options_are_rejected() {
+    rejected(Udp::builder("127.0.0.1:0").dual_stack(true));
+    rejected(Udp::builder("127.0.0.1:0").recv_buffer_size(0));
+    rejected(Udp::builder("127.0.0.1:0").send_buffer_size(0));
+    rejected(Udp::builder("127.0.0.1:0").ttl(0));
+    rejected(Udp::builder("127.0.0.1:0").ttl(256));
+    rejected(Udp::builder("127.0.0.1:0").multicast_ttl(256));
+    rejected(Udp::builder("127.0.0.1:0").tos(256));
+    rejected(Udp::builder("127.0.0.1:0").bind_device(""));
+    rejected(Udp::builder("127.0.0.1:0").bind_device("a-name-that-is-too-long"));
+}
+
+#[test]
+fn valid_options_build() {
+    let udp = Udp::builder("127.0.0.1:0")
+        .recv_buffer_size(1 << 16)
+        .send_buffer_size(1 << 16)
+        .ttl(32)
+        .multicast_ttl(4)
+        .multicast_loop(false)
+        .dscp(46)
+        .build()
+        .unwrap();
+    assert!(udp.local_addr().unwrap().ip().is_loopback());
+}
+
+#[test]
+fn reuse_port_shares_a_port() {
+    let first = Udp::builder("127.0.0.1:0")
+        .reuse_port(true)
+        .build()
+        .unwrap();
+    let addr = first.local_addr().unwrap();
+    let second = Udp::builder(addr).reuse_port(true).build().unwrap();
+    assert_eq!(second.local_addr().unwrap(), addr);
+}
+
+#[test]
+fn port_is_exclusive_without_reuse() {
+    let first = Udp::builder("127.0.0.1:0").build().unwrap();
+    let addr = first.local_addr().unwrap();
+    let err = Udp::builder(addr).build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::AddrInUse);
+}
@@ -0,0 +1,43 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::time::{Duration, Instant};
+
+use futures_lite::future::block_on;
+use yanet_core::Socket;
+use yanet_udp::Udp;
+
+#[test]
+fn round_trip_over_loopback() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+    block_on(async {
+        a.send(&1u32, b_addr).await.unwrap();
+        assert_eq!(b.recv::<u32>().await.unwrap(), (1, a_addr));
+        b.send("reply", a_addr).await.unwrap();
+        assert_eq!(a.recv::<String>().await.unwrap(), ("reply".into(), b_addr));
+    });
+}
+
+#[test]
+fn recv_wakes_on_readiness() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+    let start = Instant::now();
+    block_on(async {
+        for n in 0..20u32 {
+            a.send(&n, b_addr).await.unwrap();
+            let (n, _) = b.recv::<u32>().await.unwrap();
+            b.send(&n, a_addr).await.unwrap();
+            a.recv::<u32>().await.unwrap();
+        }
+    });
+    // Sleep polling would add up to 100 ms per recv here.
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        start.elapsed()
+    );
+}
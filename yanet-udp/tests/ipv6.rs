@@ -0,0 +1,65 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{
+    io::ErrorKind,
+    net::{Ipv6Addr, SocketAddr, SocketAddrV6},
+};
+
+use futures_lite::future::block_on;
+use yanet_core::Socket;
+use yanet_udp::Udp;
+
+#[test]
+fn round_trip_over_ipv6_loopback() {
+    let mut a = Udp::new("[::1]:0").unwrap();
+    let mut b = Udp::new("[::1]:0").unwrap();
+    let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+    block_on(async {
+        a.send(&6u32, b_addr).await.unwrap();
+        assert_eq!(b.recv::<u32>().await.unwrap(), (6, a_addr));
+    });
+}
+
+#[test]
+fn dual_stack_talks_to_ipv4_with_plain_addresses() {
+    let mut dual = Udp::new_dual_stack(0).unwrap();
+    let port = dual.local_addr().unwrap().port();
+    let mut v4 = Udp::new("127.0.0.1:0").unwrap();
+    let v4_addr = v4.local_addr().unwrap();
+    block_on(async {
+        v4.send(&4u32, SocketAddr::from(([127, 0, 0, 1], port)))
+            .await
+            .unwrap();
+        let (n, from) = dual.recv::<u32>().await.unwrap();
+        assert_eq!((n, from), (4, v4_addr));
+        dual.send(&5u32, from).await.unwrap();
+        assert_eq!(v4.recv::<u32>().await.unwrap().0, 5);
+    });
+}
+
+#[test]
+fn link_local_peers_need_a_scope() {
+    let mut udp = Udp::new("[::1]:0").unwrap();
+    let addr: Ipv6Addr = "fe80::1".parse().unwrap();
+    let err = udp.add_peer(SocketAddrV6::new(addr, 9, 0, 0)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    udp.add_peer(SocketAddrV6::new(addr, 9, 0, 1)).unwrap();
+    assert_eq!(udp.peers().count(), 1);
+}
+
+#[test]
+fn link_local_group_is_scoped_to_its_interface() {
+    let mut udp = Udp::new("[::]:0").unwrap();
+    let group: Ipv6Addr = "ff02::1:5".parse().unwrap();
+    let index = yanet_udp::interfaces()
+        .unwrap()
+        .into_iter()
+        .find(|interface| interface.is_loopback() && interface.ip().is_ipv6())
+        .and_then(|interface| interface.index)
+        .unwrap();
+    udp.join_multicast_v6(&group, index).unwrap();
+    let port = udp.local_addr().unwrap().port();
+    let peers: Vec<_> = udp.peers().collect();
+    assert_eq!(peers, vec![SocketAddrV6::new(group, port, 0, index).into()]);
+}
@@ -0,0 +1,65 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+
+use futures_lite::future::{block_on, or};
+use yanet_core::Socket;
+use yanet_udp::Udp;
+
+const GROUP: IpAddr = IpAddr::V4(Ipv4Addr::new(239, 255, 71, 3));
+
+#[test]
+fn rejects_unicast_groups() {
+    let mut udp = Udp::new("0.0.0.0:0").unwrap();
+    let err = udp
+        .join_multicast_all(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn joins_each_matching_interface_once() {
+    let mut udp = Udp::new("0.0.0.0:0").unwrap();
+    udp.join_multicast_on(GROUP, |_| true).unwrap();
+    let mut joined: Vec<_> = udp
+        .multicast_interfaces()
+        .map(|(_, interface)| interface.name.clone())
+        .collect();
+    assert!(!joined.is_empty());
+    assert!(udp.multicast_interfaces().all(|(group, _)| group == GROUP));
+    let count = joined.len();
+    joined.sort();
+    joined.dedup();
+    assert_eq!(joined.len(), count);
+    udp.refresh_multicast().unwrap();
+    assert_eq!(udp.multicast_interfaces().count(), count);
+}
+
+#[test]
+fn broadcast_reaches_the_group() {
+    let mut listener = Udp::builder("0.0.0.0:0")
+        .reuse_address(true)
+        .build()
+        .unwrap();
+    let port = listener.local_addr().unwrap().port();
+    listener.join_multicast_on(GROUP, |_| true).unwrap();
+    let mut sender = Udp::builder(("0.0.0.0", port))
+        .reuse_address(true)
+        .build()
+        .unwrap();
+    sender.join_multicast_on(GROUP, |_| true).unwrap();
+    block_on(async {
+        sender.broadcast(&9u32).await.unwrap();
+        let timeout = async {
+            async_io::Timer::after(Duration::from_secs(2)).await;
+            panic!("no multicast datagram");
+        };
+        let (n, _) = or(async { listener.recv::<u32>().await.unwrap() }, timeout).await;
+        assert_eq!(n, 9);
+    });
+}
@@ -0,0 +1,74 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{net::SocketAddr, thread, time::Duration};
+
+use futures_lite::future::block_on;
+use yanet_core::Socket;
+use yanet_udp::Udp;
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+#[test]
+fn peers_are_deduplicated_and_removable() {
+    let mut udp = Udp::new("127.0.0.1:0").unwrap();
+    udp.add_peer(addr(9000)).unwrap();
+    udp.add_peer(addr(9000)).unwrap();
+    udp.add_peer(addr(9001)).unwrap();
+    assert_eq!(
+        udp.peers().collect::<Vec<_>>(),
+        vec![addr(9000), addr(9001)]
+    );
+    assert!(udp.remove_peer(addr(9000)).unwrap());
+    assert!(!udp.remove_peer(addr(9000)).unwrap());
+    assert_eq!(udp.peers().collect::<Vec<_>>(), vec![addr(9001)]);
+}
+
+#[test]
+fn expiring_peers_drop_out() {
+    let mut udp = Udp::new("127.0.0.1:0").unwrap();
+    udp.add_peer_with_ttl(addr(9000), Duration::from_millis(50))
+        .unwrap();
+    udp.add_peer(addr(9001)).unwrap();
+    assert_eq!(udp.peers().count(), 2);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(udp.peers().collect::<Vec<_>>(), vec![addr(9001)]);
+}
+
+#[test]
+fn permanent_peers_outlive_a_ttl() {
+    let mut udp = Udp::new("127.0.0.1:0").unwrap();
+    udp.add_peer(addr(9000)).unwrap();
+    udp.add_peer_with_ttl(addr(9000), Duration::from_millis(10))
+        .unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(udp.peers().collect::<Vec<_>>(), vec![addr(9000)]);
+}
+
+#[test]
+fn learned_senders_receive_broadcasts() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    let b_addr = b.local_addr().unwrap();
+    b.learn_peers(Some(Duration::from_secs(60)));
+    block_on(async {
+        a.send(&1u32, b_addr).await.unwrap();
+        b.recv::<u32>().await.unwrap();
+        assert_eq!(b.peers().collect::<Vec<_>>(), vec![a.local_addr().unwrap()]);
+        b.broadcast(&2u32).await.unwrap();
+        assert_eq!(a.recv::<u32>().await.unwrap(), (2, b_addr));
+    });
+}
+
+#[test]
+fn senders_are_not_learned_by_default() {
+    let mut a = Udp::new("127.0.0.1:0").unwrap();
+    let mut b = Udp::new("127.0.0.1:0").unwrap();
+    block_on(async {
+        a.send(&1u32, b.local_addr().unwrap()).await.unwrap();
+        b.recv::<u32>().await.unwrap();
+    });
+    assert_eq!(b.peers().count(), 0);
+}
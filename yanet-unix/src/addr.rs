@@ -0,0 +1,82 @@
+use std::{
+    fmt, io,
+    os::unix::net,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum UnixAddr {
+    Path(PathBuf),
+    Abstract(Vec<u8>),
+    Unnamed,
+}
+
+impl UnixAddr {
+    pub fn abstract_name(name: impl Into<Vec<u8>>) -> Self {
+        Self::Abstract(name.into())
+    }
+
+    pub(crate) fn to_std(&self) -> io::Result<net::SocketAddr> {
+        match self {
+            Self::Path(path) => net::SocketAddr::from_pathname(path),
+            #[cfg(target_os = "linux")]
+            Self::Abstract(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                net::SocketAddr::from_abstract_name(name)
+            }
+            #[cfg(not(target_os = "linux"))]
+            Self::Abstract(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract socket addresses are only supported on Linux",
+            )),
+            Self::Unnamed => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unnamed socket address",
+            )),
+        }
+    }
+
+    pub(crate) fn from_std(addr: &net::SocketAddr) -> Self {
+        if let Some(path) = addr.as_pathname() {
+            return Self::Path(path.to_owned());
+        }
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            if let Some(name) = addr.as_abstract_name() {
+                return Self::Abstract(name.to_vec());
+            }
+        }
+        Self::Unnamed
+    }
+}
+
+impl From<PathBuf> for UnixAddr {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+impl From<&Path> for UnixAddr {
+    fn from(path: &Path) -> Self {
+        Self::Path(path.to_owned())
+    }
+}
+
+impl From<&str> for UnixAddr {
+    fn from(path: &str) -> Self {
+        Self::Path(path.into())
+    }
+}
+
+impl fmt::Display for UnixAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Abstract(name) => write!(f, "@{}", name.escape_ascii()),
+            Self::Unnamed => write!(f, "(unnamed)"),
+        }
+    }
+}
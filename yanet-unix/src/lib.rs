@@ -0,0 +1,116 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{
+    collections::BTreeSet,
+    io::{self, ErrorKind},
+    os::unix::net::UnixDatagram,
+};
+
+use async_io::Async;
+use serde::{de::DeserializeOwned, Serialize};
+use yanet_core::Socket;
+
+mod addr;
+pub use addr::UnixAddr;
+
+pub type BroadcastError = yanet_core::BroadcastError<UnixAddr, io::Error>;
+
+const MAX_DATAGRAM: usize = u16::MAX as usize;
+
+fn invalid_data(err: postcard::Error) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, err)
+}
+
+pub struct Unix {
+    peers: BTreeSet<UnixAddr>,
+    local: UnixAddr,
+    inner: Async<UnixDatagram>,
+}
+
+impl Unix {
+    pub fn new(addr: impl Into<UnixAddr>) -> io::Result<Self> {
+        let local = addr.into();
+        let socket = UnixDatagram::bind_addr(&local.to_std()?)?;
+        Ok(Self {
+            peers: Default::default(),
+            local,
+            inner: Async::new(socket)?,
+        })
+    }
+    pub fn unbound() -> io::Result<Self> {
+        Ok(Self {
+            peers: Default::default(),
+            local: UnixAddr::Unnamed,
+            inner: Async::new(UnixDatagram::unbound()?)?,
+        })
+    }
+    pub fn local_addr(&self) -> &UnixAddr {
+        &self.local
+    }
+    pub fn add_peer(&mut self, addr: impl Into<UnixAddr>) {
+        self.peers.insert(addr.into());
+    }
+    pub fn remove_peer(&mut self, addr: &UnixAddr) -> bool {
+        self.peers.remove(addr)
+    }
+    pub fn peers(&self) -> impl Iterator<Item = &UnixAddr> {
+        self.peers.iter()
+    }
+    async fn send_raw(&self, dat: &[u8], addr: &UnixAddr) -> io::Result<()> {
+        let target = addr.to_std()?;
+        yanet_core::trace!(peer = %addr, len = dat.len(), "sending datagram");
+        self.inner
+            .write_with(|s| s.send_to_addr(dat, &target))
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for Unix {
+    fn drop(&mut self) {
+        if let UnixAddr::Path(path) = &self.local {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+impl Socket for Unix {
+    type Addr = UnixAddr;
+    type Error = io::Error;
+
+    async fn broadcast<D>(&mut self, data: &D) -> Result<(), Self::Error>
+    where
+        D: Serialize,
+    {
+        let dat = postcard::to_allocvec(data).map_err(invalid_data)?;
+        let mut failures = Vec::new();
+        for addr in self.peers.iter() {
+            if let Err(err) = self.send_raw(&dat, addr).await {
+                yanet_core::debug!(peer = %addr, error = %err, "failed to send datagram");
+                failures.push((addr.clone(), err));
+            }
+        }
+        BroadcastError::check(failures).map_err(io::Error::other)
+    }
+    async fn send<D>(&mut self, data: &D, addr: Self::Addr) -> Result<(), Self::Error>
+    where
+        D: Serialize + ?Sized,
+    {
+        let dat = postcard::to_allocvec(data).map_err(invalid_data)?;
+        self.send_raw(&dat, &addr).await
+    }
+    async fn recv<D>(&mut self) -> Result<(D, Self::Addr), Self::Error>
+    where
+        D: DeserializeOwned,
+    {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        let (len, addr) = self.inner.recv_from(&mut buf).await?;
+        let addr = UnixAddr::from_std(&addr);
+        yanet_core::trace!(peer = %addr, len, "received datagram");
+        Ok((
+            postcard::from_bytes(&buf[..len]).map_err(invalid_data)?,
+            addr,
+        ))
+    }
+}
@@ -0,0 +1,78 @@
+#![allow(incomplete_features)]
+#![feature(async_fn_in_trait)]
+
+use std::{path::PathBuf, process};
+
+use async_io::block_on;
+use yanet_core::Socket;
+use yanet_unix::{Unix, UnixAddr};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yanet-unix-{}-{}.sock", process::id(), name))
+}
+
+#[test]
+fn round_trip_over_paths() {
+    let (a_path, b_path) = (temp_path("a"), temp_path("b"));
+    let mut a = Unix::new(a_path.clone()).unwrap();
+    let mut b = Unix::new(b_path.clone()).unwrap();
+    block_on(async {
+        a.send(&1u32, b_path.clone().into()).await.unwrap();
+        let (n, from) = b.recv::<u32>().await.unwrap();
+        assert_eq!((n, &from), (1, &UnixAddr::Path(a_path)));
+        b.send("reply", from).await.unwrap();
+        assert_eq!(a.recv::<String>().await.unwrap().0, "reply");
+    });
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn round_trip_over_abstract_names() {
+    let a_addr = UnixAddr::abstract_name(format!("yanet-{}-a", process::id()));
+    let b_addr = UnixAddr::abstract_name(format!("yanet-{}-b", process::id()));
+    let mut a = Unix::new(a_addr.clone()).unwrap();
+    let mut b = Unix::new(b_addr.clone()).unwrap();
+    block_on(async {
+        a.send(&2u32, b_addr).await.unwrap();
+        assert_eq!(b.recv::<u32>().await.unwrap(), (2, a_addr));
+    });
+}
+
+#[test]
+fn unbound_sender_is_unnamed() {
+    let path = temp_path("unbound");
+    let mut a = Unix::unbound().unwrap();
+    let mut b = Unix::new(path.clone()).unwrap();
+    block_on(async {
+        a.send(&3u32, path.into()).await.unwrap();
+        assert_eq!(b.recv::<u32>().await.unwrap(), (3, UnixAddr::Unnamed));
+    });
+}
+
+#[test]
+fn broadcast_reaches_live_peers_and_reports_the_rest() {
+    let (a_path, b_path, gone) = (temp_path("bc-a"), temp_path("bc-b"), temp_path("bc-gone"));
+    let mut a = Unix::new(a_path).unwrap();
+    let mut b = Unix::new(b_path.clone()).unwrap();
+    a.add_peer(gone.clone());
+    a.add_peer(b_path);
+    block_on(async {
+        let err = a.broadcast(&4u32).await.unwrap_err();
+        let report = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<yanet_unix::BroadcastError>())
+            .expect("broadcast error");
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, UnixAddr::Path(gone));
+        assert_eq!(b.recv::<u32>().await.unwrap().0, 4);
+    });
+}
+
+#[test]
+fn drop_removes_the_socket_file() {
+    let path = temp_path("drop");
+    let unix = Unix::new(path.clone()).unwrap();
+    assert!(path.exists());
+    drop(unix);
+    assert!(!path.exists());
+}